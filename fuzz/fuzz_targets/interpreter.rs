@@ -0,0 +1,10 @@
+#![no_main]
+
+use glad::{Lexer, Parser};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    if let Ok(statements) = Parser::new(Lexer::new(source).scan_tokens()).parse() {
+        let _ = glad_fuzz::interpreter().interpret(&statements);
+    }
+});
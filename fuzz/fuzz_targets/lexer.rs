@@ -0,0 +1,8 @@
+#![no_main]
+
+use glad::Lexer;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    Lexer::new(source).scan_tokens();
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use glad::{Lexer, Parser};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    let _ = Parser::new(Lexer::new(source).scan_tokens()).parse();
+});
@@ -0,0 +1,53 @@
+//! Setup shared by the fuzz targets.
+
+use std::io;
+
+use glad::{Capabilities, Interpreter};
+
+/// An interpreter that can't reach the machine the fuzzer runs on, or wait
+/// on anything. Printing is allowed so it still gets exercised, but the
+/// output goes nowhere.
+pub fn interpreter() -> Interpreter {
+    Interpreter::new()
+        .fuel(100_000)
+        .max_allocated(1 << 20)
+        .capabilities(Capabilities {
+            stdout: true,
+            ..Capabilities::none()
+        })
+        .output(io::sink())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use glad::{Lexer, Parser};
+
+    fn run(source: &str) -> Result<(), String> {
+        let statements = Parser::new(Lexer::new(source).scan_tokens())
+            .parse()
+            .unwrap();
+        interpreter()
+            .interpret(&statements)
+            .map_err(|error| error.kind.to_string())
+    }
+
+    #[test]
+    fn rejects_io() {
+        for source in [
+            "readFile(\"Cargo.toml\");",
+            "writeFile(\"out.txt\", \"\");",
+            "listDir(\".\");",
+            "readLine();",
+            "env(\"HOME\");",
+            "clock();",
+            "random();",
+        ] {
+            let error = run(source).unwrap_err();
+            assert!(error.starts_with("Not allowed to "), "{}: {}", source, error);
+        }
+
+        assert_eq!(run("print 1;"), Ok(()));
+    }
+}
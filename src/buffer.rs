@@ -0,0 +1,35 @@
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+/// An in-memory sink for an interpreter's output or errors. Clones share the
+/// same contents, so one can be handed to the interpreter and another kept
+/// to read back what was written.
+#[derive(Debug, Clone, Default)]
+pub struct Buffer {
+    bytes: Rc<RefCell<Vec<u8>>>,
+}
+
+impl Buffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything written so far.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.bytes.borrow()).into_owned()
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
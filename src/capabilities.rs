@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+
+/// What a script may do to the world outside the interpreter. Natives check
+/// these before touching anything, and raise a runtime error if they're not
+/// allowed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    /// Directories whose contents may be read, or `None` for anywhere.
+    pub read: Option<Vec<PathBuf>>,
+    /// Directories whose contents may be written, or `None` for anywhere.
+    pub write: Option<Vec<PathBuf>>,
+    /// Reading environment variables.
+    pub env: bool,
+    /// Reading from standard input.
+    pub stdin: bool,
+    /// Reading the time.
+    pub clock: bool,
+    /// Generating random numbers.
+    pub random: bool,
+    /// Printing to standard output.
+    pub stdout: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl Capabilities {
+    /// Everything is allowed, as for a script the user is running
+    /// themselves.
+    pub fn all() -> Self {
+        Self {
+            read: None,
+            write: None,
+            env: true,
+            stdin: true,
+            clock: true,
+            random: true,
+            stdout: true,
+        }
+    }
+
+    /// Nothing is allowed, for running untrusted code. Scripts can still
+    /// compute a result, and allowing `stdout` again lets them print it.
+    pub fn none() -> Self {
+        Self {
+            read: Some(Vec::new()),
+            write: Some(Vec::new()),
+            env: false,
+            stdin: false,
+            clock: false,
+            random: false,
+            stdout: false,
+        }
+    }
+
+    pub fn can_read(&self, path: &Path) -> bool {
+        is_under(&self.read, path)
+    }
+
+    pub fn can_write(&self, path: &Path) -> bool {
+        is_under(&self.write, path)
+    }
+}
+
+fn is_under(roots: &Option<Vec<PathBuf>>, path: &Path) -> bool {
+    let roots = match roots {
+        Some(roots) => roots,
+        None => return true,
+    };
+
+    // Resolve `..` and symlinks first, so they can't be used to get out of
+    // a root.
+    let path = match resolve(path) {
+        Some(path) => path,
+        None => return false,
+    };
+
+    roots
+        .iter()
+        .any(|root| root.canonicalize().is_ok_and(|root| path.starts_with(root)))
+}
+
+/// The canonical form of `path`. A file that doesn't exist yet, such as one
+/// about to be written, is resolved through its parent directory.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    if let Ok(path) = path.canonicalize() {
+        return Some(path);
+    }
+
+    let parent = match path.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => Path::new("."),
+        Some(parent) => parent,
+        None => return None,
+    };
+    let name = path.file_name()?;
+    parent.canonicalize().ok().map(|parent| parent.join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    #[test]
+    fn roots() {
+        let root = env::temp_dir().join(format!("glad-capabilities-{}", std::process::id()));
+        fs::create_dir_all(root.join("inside")).unwrap();
+        fs::write(root.join("inside/file.txt"), "").unwrap();
+
+        let capabilities = Capabilities {
+            read: Some(vec![root.join("inside")]),
+            ..Capabilities::none()
+        };
+        assert!(capabilities.can_read(&root.join("inside/file.txt")));
+        assert!(capabilities.can_read(&root.join("inside/new.txt")));
+        assert!(!capabilities.can_read(&root.join("inside/../outside.txt")));
+        assert!(!capabilities.can_read(&root.join("inside/missing/file.txt")));
+        assert!(!capabilities.can_write(&root.join("inside/file.txt")));
+
+        assert!(Capabilities::all().can_write(&root.join("anything")));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
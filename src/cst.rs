@@ -0,0 +1,1229 @@
+use std::{fmt, ops::Range, rc::Rc};
+
+use crate::{
+    interpreter::{RED_ZONE, STACK_SEGMENT},
+    lexer::Lexer,
+    parser::MAX_NESTING,
+    token::Token,
+    token_type::TokenType,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxKind {
+    // Nodes.
+    Root,
+    VarDecl,
+    ImportDecl,
+    PrintStmt,
+    ExprStmt,
+    Block,
+    ForStmt,
+    IfStmt,
+    WhileStmt,
+    Assign,
+    Variable,
+    Literal,
+    Grouping,
+    Unary,
+    Binary,
+    Call,
+    /// The parenthesised arguments of a call.
+    Arguments,
+    Get,
+    List,
+    Map,
+    Index,
+    /// Wraps tokens that don't fit the grammar where they appear.
+    Error,
+    /// Stands in for something the grammar requires but the source lacks.
+    /// Always empty.
+    Missing,
+
+    // Leaves, including trivia and anything the lexer didn't recognise.
+    Token(TokenType),
+}
+
+impl SyntaxKind {
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            Self::Token(TokenType::Whitespace) | Self::Token(TokenType::Comment)
+        )
+    }
+}
+
+/// An immutable leaf of the green tree. Green elements know their text and
+/// width, but not where they are, so identical subtrees can be shared.
+#[derive(Debug, PartialEq)]
+pub struct GreenToken {
+    kind: SyntaxKind,
+    text: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    width: usize,
+    children: Vec<GreenElement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenElement {
+    fn width(&self) -> usize {
+        match self {
+            Self::Node(node) => node.width,
+            Self::Token(token) => token.text.len(),
+        }
+    }
+}
+
+/// A node of the red tree: a view onto a green node that also knows its
+/// parent and absolute offset in the source. Red nodes are created lazily as
+/// the tree is walked, and are cheap to clone.
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+struct NodeData {
+    green: Rc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    offset: usize,
+}
+
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    parent: SyntaxNode,
+    offset: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Rc<GreenNode>) -> Self {
+        Self(Rc::new(NodeData {
+            green,
+            parent: None,
+            offset: 0,
+        }))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind.clone()
+    }
+
+    /// Byte range of the node in the source, including any trivia inside it.
+    pub fn text_range(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.width
+    }
+
+    pub fn text(&self) -> String {
+        self.children_with_tokens()
+            .into_iter()
+            .map(|child| match child {
+                SyntaxElement::Node(node) => node.text(),
+                SyntaxElement::Token(token) => token.text().to_owned(),
+            })
+            .collect()
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.clone()
+    }
+
+    pub fn children(&self) -> Vec<SyntaxNode> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|child| match child {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect()
+    }
+
+    pub fn children_with_tokens(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+
+        self.0
+            .green
+            .children
+            .iter()
+            .map(|child| {
+                let element = match child {
+                    GreenElement::Node(green) => SyntaxElement::Node(Self(Rc::new(NodeData {
+                        green: green.clone(),
+                        parent: Some(self.clone()),
+                        offset,
+                    }))),
+                    GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                        green: green.clone(),
+                        parent: self.clone(),
+                        offset,
+                    }),
+                };
+                offset += child.width();
+                element
+            })
+            .collect()
+    }
+
+    /// Finds the token covering the byte at `offset`, if there is one.
+    pub fn token_at_offset(&self, offset: usize) -> Option<SyntaxToken> {
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) if node.text_range().contains(&offset) => {
+                    return node.token_at_offset(offset);
+                }
+                SyntaxElement::Token(token) if token.text_range().contains(&offset) => {
+                    return Some(token);
+                }
+                _ => {}
+            }
+        }
+
+        None
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind.clone()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn text_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text.len()
+    }
+
+    pub fn parent(&self) -> SyntaxNode {
+        self.parent.clone()
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+
+/// Dumps the tree one element per line, indented by depth.
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let depth = f.width().unwrap_or(0);
+        let range = self.text_range();
+        writeln!(
+            f,
+            "{:indent$}{:?}@{}..{}",
+            "",
+            self.kind(),
+            range.start,
+            range.end,
+            indent = depth * 2
+        )?;
+
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) => write!(f, "{:depth$?}", node, depth = depth + 1)?,
+                SyntaxElement::Token(token) => {
+                    write!(f, "{:indent$}{:?}", "", token, indent = (depth + 1) * 2)?
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = self.text_range();
+        let kind = match self.kind() {
+            SyntaxKind::Token(token_type) => format!("{:?}", token_type),
+            kind => format!("{:?}", kind),
+        };
+        writeln!(
+            f,
+            "{}@{}..{} {:?}",
+            kind,
+            range.start,
+            range.end,
+            self.text()
+        )
+    }
+}
+
+/// Builds a green tree bottom-up as the parser walks the source.
+struct Builder {
+    parents: Vec<(SyntaxKind, usize)>,
+    children: Vec<GreenElement>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self {
+            parents: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn token(&mut self, kind: SyntaxKind, text: String) {
+        self.children
+            .push(GreenElement::Token(Rc::new(GreenToken { kind, text })));
+    }
+
+    fn start_node(&mut self, kind: SyntaxKind) {
+        self.parents.push((kind, self.children.len()));
+    }
+
+    /// Remembers the current position, so a node can later be started there
+    /// to wrap everything built since, e.g. the left operand of a binary.
+    fn checkpoint(&self) -> usize {
+        self.children.len()
+    }
+
+    fn start_node_at(&mut self, checkpoint: usize, kind: SyntaxKind) {
+        self.parents.push((kind, checkpoint));
+    }
+
+    fn finish_node(&mut self) {
+        let (kind, first_child) = self.parents.pop().unwrap();
+        let children: Vec<GreenElement> = self.children.drain(first_child..).collect();
+        let width = children.iter().map(GreenElement::width).sum();
+
+        self.children.push(GreenElement::Node(Rc::new(GreenNode {
+            kind,
+            width,
+            children,
+        })));
+    }
+
+    /// The kind of the most recently finished node, if it was the last
+    /// thing built.
+    fn last_kind(&self) -> Option<&SyntaxKind> {
+        match self.children.last() {
+            Some(GreenElement::Node(node)) => Some(&node.kind),
+            _ => None,
+        }
+    }
+
+    fn finish(mut self) -> Rc<GreenNode> {
+        match self.children.pop() {
+            Some(GreenElement::Node(root)) if self.children.is_empty() => root,
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub range: Range<usize>,
+}
+
+/// The result of parsing: a tree covering every byte of the source, and the
+/// problems found on the way.
+pub struct Parse {
+    green: Rc<GreenNode>,
+    errors: Vec<SyntaxError>,
+}
+
+impl Parse {
+    pub fn syntax(&self) -> SyntaxNode {
+        SyntaxNode::new_root(self.green.clone())
+    }
+
+    pub fn errors(&self) -> &[SyntaxError] {
+        &self.errors
+    }
+}
+
+/// Parses `source` into a lossless syntax tree. This never fails: input that
+/// doesn't fit the grammar ends up in `Error` nodes, and anything expected
+/// but absent is marked with a `Missing` node.
+pub fn parse(source: &str) -> Parse {
+    let tokens = Lexer::new(source).lossless().scan_tokens();
+    let mut parser = CstParser {
+        tokens,
+        current: 0,
+        offset: 0,
+        depth: 0,
+        builder: Builder::new(),
+        errors: Vec::new(),
+    };
+
+    parser.root();
+
+    Parse {
+        green: parser.builder.finish(),
+        errors: parser.errors,
+    }
+}
+
+const BINARY_OPERATORS: [&[TokenType]; 4] = [
+    &[TokenType::BangEqual, TokenType::EqualEqual],
+    &[
+        TokenType::Greater,
+        TokenType::GreaterEqual,
+        TokenType::Less,
+        TokenType::LessEqual,
+    ],
+    &[TokenType::Minus, TokenType::Plus],
+    &[TokenType::Slash, TokenType::Star, TokenType::Percent],
+];
+
+/// Tokens that can start a statement. When something is missing, these are
+/// left alone so the statement they begin still parses.
+const STATEMENT_START: [TokenType; 7] = [
+    TokenType::Var,
+    TokenType::Import,
+    TokenType::For,
+    TokenType::If,
+    TokenType::While,
+    TokenType::Print,
+    TokenType::LeftBrace,
+];
+
+/// Mirrors the grammar of `Parser`, but recovers from every error instead of
+/// bailing out.
+struct CstParser {
+    tokens: Vec<Token>,
+    current: usize,
+    /// Byte offset of `tokens[current]`.
+    offset: usize,
+    /// How deeply nested the tree being built is at this point.
+    depth: usize,
+    builder: Builder,
+    errors: Vec<SyntaxError>,
+}
+
+impl CstParser {
+    fn root(&mut self) {
+        self.builder.start_node(SyntaxKind::Root);
+
+        while !self.at(&[TokenType::Eof]) {
+            self.declaration();
+        }
+
+        self.eat_trivia();
+        self.builder.finish_node();
+    }
+
+    fn declaration(&mut self) {
+        let start = self.current;
+
+        if self.at(&[TokenType::Var]) {
+            self.var_declaration();
+        } else if self.at(&[TokenType::Import]) {
+            self.import_declaration();
+        } else {
+            self.statement();
+        }
+
+        // Make sure we always move forward, even if the statement was made
+        // of nothing but missing pieces.
+        if self.current == start {
+            self.eat_trivia();
+            self.builder.start_node(SyntaxKind::Error);
+            self.bump();
+            self.builder.finish_node();
+        }
+    }
+
+    fn var_declaration(&mut self) {
+        self.eat_trivia();
+        self.builder.start_node(SyntaxKind::VarDecl);
+        self.bump();
+        self.expect(TokenType::Identifier, "Expect variable name.");
+
+        if self.at(&[TokenType::Equal]) {
+            self.bump();
+            self.expression();
+        }
+
+        self.expect(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        );
+        self.builder.finish_node();
+    }
+
+    fn import_declaration(&mut self) {
+        self.eat_trivia();
+        self.builder.start_node(SyntaxKind::ImportDecl);
+        self.bump();
+        self.expect(TokenType::String, "Expect module path after 'import'.");
+        self.expect(TokenType::As, "Expect 'as' after module path.");
+        self.expect(TokenType::Identifier, "Expect module name after 'as'.");
+        self.expect(TokenType::Semicolon, "Expect ';' after import.");
+        self.builder.finish_node();
+    }
+
+    fn statement(&mut self) {
+        self.eat_trivia();
+
+        if self.at(&[TokenType::For]) {
+            self.builder.start_node(SyntaxKind::ForStmt);
+            self.bump();
+            self.expect(TokenType::LeftParen, "Expect '(' after 'for'.");
+            self.expect(TokenType::Var, "Expect 'var' after '('.");
+            self.expect(TokenType::Identifier, "Expect variable name.");
+            self.expect(TokenType::In, "Expect 'in' after loop variable.");
+            self.expression();
+            self.expect(TokenType::RightParen, "Expect ')' after for clauses.");
+            self.nested(true, Self::statement);
+            self.builder.finish_node();
+        } else if self.at(&[TokenType::If]) {
+            self.builder.start_node(SyntaxKind::IfStmt);
+            self.bump();
+            self.expect(TokenType::LeftParen, "Expect '(' after 'if'.");
+            self.expression();
+            self.expect(TokenType::RightParen, "Expect ')' after if condition.");
+            self.nested(true, Self::statement);
+
+            if self.at(&[TokenType::Else]) {
+                self.bump();
+                self.nested(true, Self::statement);
+            }
+
+            self.builder.finish_node();
+        } else if self.at(&[TokenType::While]) {
+            self.builder.start_node(SyntaxKind::WhileStmt);
+            self.bump();
+            self.expect(TokenType::LeftParen, "Expect '(' after 'while'.");
+            self.expression();
+            self.expect(TokenType::RightParen, "Expect ')' after condition.");
+            self.nested(true, Self::statement);
+            self.builder.finish_node();
+        } else if self.at(&[TokenType::Print]) {
+            self.builder.start_node(SyntaxKind::PrintStmt);
+            self.bump();
+            self.expression();
+            self.expect(TokenType::Semicolon, "Expect ';' after value.");
+            self.builder.finish_node();
+        } else if self.at(&[TokenType::LeftBrace]) && !self.starts_map() {
+            self.block();
+        } else {
+            self.builder.start_node(SyntaxKind::ExprStmt);
+            self.expression();
+            self.expect(TokenType::Semicolon, "Expect ';' after expression.");
+            self.builder.finish_node();
+        }
+    }
+
+    /// Whether the `{` starting a statement opens a map, like
+    /// `Parser::starts_map`.
+    fn starts_map(&self) -> bool {
+        matches!(
+            self.nth_significant(1),
+            TokenType::String
+                | TokenType::Number
+                | TokenType::Identifier
+                | TokenType::True
+                | TokenType::False
+                | TokenType::Nil
+        ) && self.nth_significant(2) == TokenType::Colon
+    }
+
+    fn block(&mut self) {
+        self.builder.start_node(SyntaxKind::Block);
+        self.bump();
+
+        while !self.at(&[TokenType::RightBrace, TokenType::Eof]) {
+            self.nested(true, Self::declaration);
+        }
+
+        self.expect(TokenType::RightBrace, "Expect '}' after block.");
+        self.builder.finish_node();
+    }
+
+    fn expression(&mut self) {
+        self.nested(false, Self::assignment);
+    }
+
+    fn assignment(&mut self) {
+        self.eat_trivia();
+        let checkpoint = self.builder.checkpoint();
+        self.binary(0);
+
+        if self.at(&[TokenType::Equal]) {
+            if !matches!(
+                self.builder.last_kind(),
+                Some(SyntaxKind::Variable | SyntaxKind::Index)
+            ) {
+                self.error("Invalid assignment target.");
+            }
+
+            self.builder.start_node_at(checkpoint, SyntaxKind::Assign);
+            self.bump();
+            self.nested(false, Self::assignment);
+            self.builder.finish_node();
+        }
+    }
+
+    fn binary(&mut self, level: usize) {
+        if level == BINARY_OPERATORS.len() {
+            return self.unary();
+        }
+
+        self.eat_trivia();
+        let depth = self.depth;
+        let checkpoint = self.builder.checkpoint();
+        self.binary(level + 1);
+
+        while self.at(BINARY_OPERATORS[level]) {
+            if !self.deepen(false) {
+                break;
+            }
+            self.builder.start_node_at(checkpoint, SyntaxKind::Binary);
+            self.bump();
+            self.binary(level + 1);
+            self.builder.finish_node();
+        }
+        self.depth = depth;
+    }
+
+    fn unary(&mut self) {
+        if self.at(&[TokenType::Bang, TokenType::Minus]) {
+            self.eat_trivia();
+            self.builder.start_node(SyntaxKind::Unary);
+            self.bump();
+            self.nested(false, Self::unary);
+            self.builder.finish_node();
+        } else {
+            self.call();
+        }
+    }
+
+    fn call(&mut self) {
+        self.eat_trivia();
+        let depth = self.depth;
+        let checkpoint = self.builder.checkpoint();
+        self.primary();
+
+        while self.at(&[TokenType::LeftParen, TokenType::Dot, TokenType::LeftBracket]) {
+            if !self.deepen(false) {
+                break;
+            }
+
+            if self.at(&[TokenType::LeftParen]) {
+                self.builder.start_node_at(checkpoint, SyntaxKind::Call);
+                self.arguments();
+            } else if self.at(&[TokenType::Dot]) {
+                self.builder.start_node_at(checkpoint, SyntaxKind::Get);
+                self.bump();
+                self.expect(TokenType::Identifier, "Expect property name after '.'.");
+            } else if self.at(&[TokenType::LeftBracket]) {
+                self.builder.start_node_at(checkpoint, SyntaxKind::Index);
+                self.bump();
+                self.expression();
+                self.expect(TokenType::RightBracket, "Expect ']' after index.");
+            }
+            self.builder.finish_node();
+        }
+        self.depth = depth;
+    }
+
+    fn arguments(&mut self) {
+        self.eat_trivia();
+        self.builder.start_node(SyntaxKind::Arguments);
+        self.bump();
+        self.expressions(TokenType::RightParen);
+        self.expect(TokenType::RightParen, "Expect ')' after arguments.");
+        self.builder.finish_node();
+    }
+
+    /// A comma-separated list of expressions, up to but not including
+    /// `end`.
+    fn expressions(&mut self, end: TokenType) {
+        if self.at(&[end]) {
+            return;
+        }
+
+        loop {
+            self.expression();
+            if !self.at(&[TokenType::Comma]) {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    fn primary(&mut self) {
+        if self.at(&[
+            TokenType::False,
+            TokenType::True,
+            TokenType::Nil,
+            TokenType::Number,
+            TokenType::String,
+        ]) {
+            self.eat_trivia();
+            self.builder.start_node(SyntaxKind::Literal);
+            self.bump();
+            self.builder.finish_node();
+        } else if self.at(&[TokenType::Identifier]) {
+            self.eat_trivia();
+            self.builder.start_node(SyntaxKind::Variable);
+            self.bump();
+            self.builder.finish_node();
+        } else if self.at(&[TokenType::LeftBracket]) {
+            self.eat_trivia();
+            self.builder.start_node(SyntaxKind::List);
+            self.bump();
+            self.expressions(TokenType::RightBracket);
+            self.expect(TokenType::RightBracket, "Expect ']' after list elements.");
+            self.builder.finish_node();
+        } else if self.at(&[TokenType::LeftBrace]) {
+            self.eat_trivia();
+            self.builder.start_node(SyntaxKind::Map);
+            self.bump();
+
+            if !self.at(&[TokenType::RightBrace]) {
+                loop {
+                    self.expression();
+                    self.expect(TokenType::Colon, "Expect ':' after map key.");
+                    self.expression();
+                    if !self.at(&[TokenType::Comma]) {
+                        break;
+                    }
+                    self.bump();
+                }
+            }
+
+            self.expect(TokenType::RightBrace, "Expect '}' after map entries.");
+            self.builder.finish_node();
+        } else if self.at(&[TokenType::LeftParen]) {
+            self.eat_trivia();
+            self.builder.start_node(SyntaxKind::Grouping);
+            self.bump();
+            self.expression();
+            self.expect(TokenType::RightParen, "Expect ')' after expression.");
+            self.builder.finish_node();
+        } else {
+            self.error("Expect expression.");
+
+            // Leave tokens that something further up can make sense of.
+            let recoverable = self.at(&[
+                TokenType::RightParen,
+                TokenType::RightBracket,
+                TokenType::Comma,
+                TokenType::Colon,
+                TokenType::RightBrace,
+                TokenType::Semicolon,
+                TokenType::Equal,
+                TokenType::Eof,
+            ]) || self.at(&STATEMENT_START)
+                || BINARY_OPERATORS.iter().any(|operators| self.at(operators));
+
+            if recoverable {
+                self.missing();
+            } else {
+                self.eat_trivia();
+                self.builder.start_node(SyntaxKind::Error);
+                self.bump();
+                self.builder.finish_node();
+            }
+        }
+    }
+
+    /// Runs `parse` one level deeper in the tree, like `Parser::nested`.
+    fn nested(&mut self, statement: bool, parse: impl FnOnce(&mut Self)) {
+        if self.deepen(statement) {
+            stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || parse(self));
+            self.depth -= 1;
+        }
+    }
+
+    /// Goes one level deeper in the tree. Past `MAX_NESTING`, reports an
+    /// error and wraps the statement or expression that would have gone
+    /// deeper in an error node instead, returning false.
+    fn deepen(&mut self, statement: bool) -> bool {
+        if self.depth < MAX_NESTING {
+            self.depth += 1;
+            return true;
+        }
+
+        self.error("Too much nesting.");
+        let opening = [
+            TokenType::LeftParen,
+            TokenType::LeftBracket,
+            TokenType::LeftBrace,
+        ];
+        let closing = [
+            TokenType::RightParen,
+            TokenType::RightBracket,
+            TokenType::RightBrace,
+        ];
+        // Where an expression ends, unless it's inside brackets of its own.
+        let ends = [
+            TokenType::RightParen,
+            TokenType::RightBracket,
+            TokenType::RightBrace,
+            TokenType::Semicolon,
+            TokenType::Comma,
+            TokenType::Colon,
+        ];
+
+        let end = |parser: &Self| {
+            parser.at(&[TokenType::Eof, TokenType::RightBrace]) || !statement && parser.at(&ends)
+        };
+        if end(self) {
+            self.missing();
+            return false;
+        }
+
+        self.eat_trivia();
+        self.builder.start_node(SyntaxKind::Error);
+        let mut open = 0usize;
+        while !self.at(&[TokenType::Eof]) && (open > 0 || !end(self)) {
+            let closes = self.at(&closing);
+            let semicolon = self.at(&[TokenType::Semicolon]);
+            if self.at(&opening) {
+                open += 1;
+            } else if closes {
+                open = open.saturating_sub(1);
+            }
+            self.bump();
+
+            if open == 0 && (closes || semicolon && statement) {
+                break;
+            }
+        }
+        self.builder.finish_node();
+
+        false
+    }
+
+    fn expect(&mut self, token_type: TokenType, message: &str) {
+        if self.at(&[token_type]) {
+            self.bump();
+        } else {
+            self.error(message);
+            self.missing();
+        }
+    }
+
+    fn missing(&mut self) {
+        self.builder.start_node(SyntaxKind::Missing);
+        self.builder.finish_node();
+    }
+
+    fn error(&mut self, message: &str) {
+        let (index, offset) = self.next_significant();
+        let width = self.tokens[index].get_lexeme().len();
+
+        self.errors.push(SyntaxError {
+            message: message.to_owned(),
+            range: offset..offset + width,
+        });
+    }
+
+    fn at(&self, token_types: &[TokenType]) -> bool {
+        let (index, _) = self.next_significant();
+        token_types.contains(&self.tokens[index].token_type)
+    }
+
+    /// The type of the `n`th token from here that isn't trivia, counting
+    /// from zero.
+    fn nth_significant(&self, n: usize) -> TokenType {
+        self.tokens[self.current..]
+            .iter()
+            .filter(|token| !SyntaxKind::Token(token.token_type.clone()).is_trivia())
+            .nth(n)
+            .map_or(TokenType::Eof, |token| token.token_type.clone())
+    }
+
+    /// Index and byte offset of the next token that isn't trivia.
+    fn next_significant(&self) -> (usize, usize) {
+        let mut index = self.current;
+        let mut offset = self.offset;
+
+        while SyntaxKind::Token(self.tokens[index].token_type.clone()).is_trivia() {
+            offset += self.tokens[index].get_lexeme().len();
+            index += 1;
+        }
+
+        (index, offset)
+    }
+
+    fn eat_trivia(&mut self) {
+        while SyntaxKind::Token(self.tokens[self.current].token_type.clone()).is_trivia() {
+            self.push_current();
+        }
+    }
+
+    fn bump(&mut self) {
+        self.eat_trivia();
+        self.push_current();
+    }
+
+    fn push_current(&mut self) {
+        let token = &self.tokens[self.current];
+        let text = token.get_lexeme();
+
+        self.offset += text.len();
+        self.builder
+            .token(SyntaxKind::Token(token.token_type.clone()), text);
+        self.current += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proptest::prelude::*;
+
+    fn dump(source: &str) -> String {
+        format!("{:?}", parse(source).syntax())
+    }
+
+    fn messages(source: &str) -> Vec<String> {
+        parse(source)
+            .errors()
+            .iter()
+            .map(|error| error.message.clone())
+            .collect()
+    }
+
+    #[test]
+    fn valid() {
+        let source = "var a = -1;\n{ a = (2); } // c";
+
+        assert_eq!(
+            dump(source),
+            r#"Root@0..29
+  VarDecl@0..11
+    Var@0..3 "var"
+    Whitespace@3..4 " "
+    Identifier@4..5 "a"
+    Whitespace@5..6 " "
+    Equal@6..7 "="
+    Whitespace@7..8 " "
+    Unary@8..10
+      Minus@8..9 "-"
+      Literal@9..10
+        Number@9..10 "1"
+    Semicolon@10..11 ";"
+  Whitespace@11..12 "\n"
+  Block@12..24
+    LeftBrace@12..13 "{"
+    Whitespace@13..14 " "
+    ExprStmt@14..22
+      Assign@14..21
+        Variable@14..15
+          Identifier@14..15 "a"
+        Whitespace@15..16 " "
+        Equal@16..17 "="
+        Whitespace@17..18 " "
+        Grouping@18..21
+          LeftParen@18..19 "("
+          Literal@19..20
+            Number@19..20 "2"
+          RightParen@20..21 ")"
+      Semicolon@21..22 ";"
+    Whitespace@22..23 " "
+    RightBrace@23..24 "}"
+  Whitespace@24..25 " "
+  Comment@25..29 "// c"
+"#
+        );
+        assert!(parse(source).errors().is_empty());
+    }
+
+    #[test]
+    fn missing() {
+        assert_eq!(
+            dump("print (1 +"),
+            r#"Root@0..10
+  PrintStmt@0..10
+    Print@0..5 "print"
+    Whitespace@5..6 " "
+    Grouping@6..10
+      LeftParen@6..7 "("
+      Binary@7..10
+        Literal@7..8
+          Number@7..8 "1"
+        Whitespace@8..9 " "
+        Plus@9..10 "+"
+        Missing@10..10
+      Missing@10..10
+    Missing@10..10
+"#
+        );
+        assert_eq!(
+            messages("print (1 +"),
+            vec![
+                "Expect expression.",
+                "Expect ')' after expression.",
+                "Expect ';' after value."
+            ]
+        );
+    }
+
+    #[test]
+    fn error() {
+        assert_eq!(
+            dump(") var"),
+            r#"Root@0..5
+  ExprStmt@0..0
+    Missing@0..0
+    Missing@0..0
+  Error@0..1
+    RightParen@0..1 ")"
+  Whitespace@1..2 " "
+  VarDecl@2..5
+    Var@2..5 "var"
+    Missing@5..5
+    Missing@5..5
+"#
+        );
+
+        let parse = parse("1 + @ * 2;");
+        assert_eq!(parse.errors()[0].range, 4..5);
+        assert!(parse.syntax().children()[0].children()[0]
+            .children()
+            .iter()
+            .any(|node| node.kind() == SyntaxKind::Binary));
+    }
+
+    #[test]
+    fn calls() {
+        assert_eq!(
+            dump("f(1,)"),
+            r#"Root@0..5
+  ExprStmt@0..5
+    Call@0..5
+      Variable@0..1
+        Identifier@0..1 "f"
+      Arguments@1..5
+        LeftParen@1..2 "("
+        Literal@2..3
+          Number@2..3 "1"
+        Comma@3..4 ","
+        Missing@4..4
+        RightParen@4..5 ")"
+    Missing@5..5
+"#
+        );
+        assert_eq!(
+            messages("f(1,)"),
+            vec!["Expect expression.", "Expect ';' after expression."]
+        );
+        assert_eq!(messages("f() = 1;"), vec!["Invalid assignment target."]);
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(
+            dump("[a][0].b"),
+            r#"Root@0..8
+  ExprStmt@0..8
+    Get@0..8
+      Index@0..6
+        List@0..3
+          LeftBracket@0..1 "["
+          Variable@1..2
+            Identifier@1..2 "a"
+          RightBracket@2..3 "]"
+        LeftBracket@3..4 "["
+        Literal@4..5
+          Number@4..5 "0"
+        RightBracket@5..6 "]"
+      Dot@6..7 "."
+      Identifier@7..8 "b"
+    Missing@8..8
+"#
+        );
+        assert!(parse("a[0] = [];").errors().is_empty());
+        assert!(parse("{a: {}}[a] = {1: 2, \"b\": [3]};")
+            .errors()
+            .is_empty());
+        assert_eq!(
+            parse("{a;}").syntax().children()[0].kind(),
+            SyntaxKind::Block
+        );
+        assert_eq!(
+            messages("print {1: 2, 3};"),
+            vec!["Expect ':' after map key.", "Expect expression."]
+        );
+        assert_eq!(messages("a.b = 1;"), vec!["Invalid assignment target."]);
+        assert_eq!(messages("[1, 2;"), vec!["Expect ']' after list elements."]);
+    }
+
+    #[test]
+    fn for_in() {
+        assert_eq!(
+            dump("for (var x in xs) x;"),
+            r#"Root@0..20
+  ForStmt@0..20
+    For@0..3 "for"
+    Whitespace@3..4 " "
+    LeftParen@4..5 "("
+    Var@5..8 "var"
+    Whitespace@8..9 " "
+    Identifier@9..10 "x"
+    Whitespace@10..11 " "
+    In@11..13 "in"
+    Whitespace@13..14 " "
+    Variable@14..16
+      Identifier@14..16 "xs"
+    RightParen@16..17 ")"
+    Whitespace@17..18 " "
+    ExprStmt@18..20
+      Variable@18..19
+        Identifier@18..19 "x"
+      Semicolon@19..20 ";"
+"#
+        );
+        assert_eq!(
+            messages("for (x in xs) {}"),
+            vec!["Expect 'var' after '('."]
+        );
+        assert_eq!(
+            messages("for (var x in) print x;"),
+            vec!["Expect expression."]
+        );
+    }
+
+    #[test]
+    fn if_while() {
+        assert_eq!(
+            dump("if (a) b; else c;"),
+            r#"Root@0..17
+  IfStmt@0..17
+    If@0..2 "if"
+    Whitespace@2..3 " "
+    LeftParen@3..4 "("
+    Variable@4..5
+      Identifier@4..5 "a"
+    RightParen@5..6 ")"
+    Whitespace@6..7 " "
+    ExprStmt@7..9
+      Variable@7..8
+        Identifier@7..8 "b"
+      Semicolon@8..9 ";"
+    Whitespace@9..10 " "
+    Else@10..14 "else"
+    Whitespace@14..15 " "
+    ExprStmt@15..17
+      Variable@15..16
+        Identifier@15..16 "c"
+      Semicolon@16..17 ";"
+"#
+        );
+        assert_eq!(
+            parse("while (a) {}").syntax().children()[0].kind(),
+            SyntaxKind::WhileStmt
+        );
+        assert_eq!(
+            messages("if a print 1;"),
+            vec!["Expect '(' after 'if'.", "Expect ')' after if condition."]
+        );
+        assert_eq!(
+            messages("while (a print 1;"),
+            vec!["Expect ')' after condition."]
+        );
+        assert!(parse("if (a) if (b) print 1; else print 2;")
+            .errors()
+            .is_empty());
+    }
+
+    #[test]
+    fn imports() {
+        assert_eq!(
+            dump("import \"a.lox\" as a;"),
+            r#"Root@0..20
+  ImportDecl@0..20
+    Import@0..6 "import"
+    Whitespace@6..7 " "
+    String@7..14 "\"a.lox\""
+    Whitespace@14..15 " "
+    As@15..17 "as"
+    Whitespace@17..18 " "
+    Identifier@18..19 "a"
+    Semicolon@19..20 ";"
+"#
+        );
+        assert_eq!(
+            messages("import a;\nprint 1;"),
+            vec![
+                "Expect module path after 'import'.",
+                "Expect 'as' after module path."
+            ]
+        );
+        assert_eq!(
+            messages("import \"a.lox\" a;"),
+            vec!["Expect 'as' after module path."]
+        );
+    }
+
+    #[test]
+    fn nesting() {
+        let deep = format!(
+            "print {}1{} + 2;\n{}print 3;{}",
+            "(".repeat(MAX_NESTING + 10),
+            ")".repeat(MAX_NESTING + 10),
+            "{".repeat(MAX_NESTING + 10),
+            "}".repeat(MAX_NESTING + 10)
+        );
+        assert_eq!(
+            messages(&deep),
+            vec!["Too much nesting.", "Too much nesting."]
+        );
+        assert_eq!(parse(&deep).syntax().text(), deep);
+
+        let sum = format!("print {};", vec!["1"; MAX_NESTING + 10].join(" + "));
+        assert_eq!(messages(&sum), vec!["Too much nesting."]);
+        assert_eq!(
+            messages(&format!("print {}1;", "-".repeat(100_000))).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn invalid_assignment_target() {
+        let parse = parse("a + b = c;");
+        assert_eq!(messages("a + b = c;"), vec!["Invalid assignment target."]);
+        assert_eq!(parse.errors()[0].range, 6..7);
+        assert_eq!(
+            parse.syntax().children()[0].children()[0].kind(),
+            SyntaxKind::Assign
+        );
+    }
+
+    #[test]
+    fn token_at_offset() {
+        let root = parse("1 + (2);").syntax();
+        let token = root.token_at_offset(5).unwrap();
+
+        assert_eq!(token.kind(), SyntaxKind::Token(TokenType::Number));
+        assert_eq!(token.parent().kind(), SyntaxKind::Literal);
+        assert_eq!(
+            token.parent().parent().unwrap().kind(),
+            SyntaxKind::Grouping
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn lossless(source in "\\PC*") {
+            prop_assert_eq!(parse(&source).syntax().text(), source);
+        }
+
+        #[test]
+        fn lossless_lox(source in "[-+*/%!=<>(){}\\[\\]:., \n0-9a-z\"]*") {
+            prop_assert_eq!(parse(&source).syntax().text(), source);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    interpreter::{ErrorKind, RuntimeError},
+    object::Object,
+    token::Token,
+};
+
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Object>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    pub fn define(&mut self, name: String, value: Object) {
+        self.values.insert(name, value);
+    }
+
+    pub fn values(&self) -> Vec<(String, Object)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    /// The value of a variable defined in this environment itself, not in
+    /// any enclosing one.
+    pub fn get_local(&self, name: &str) -> Option<Object> {
+        self.values.get(name).cloned()
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Object, RuntimeError> {
+        if let Some(value) = self.values.get(&name.get_lexeme()) {
+            return Ok(value.clone());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(ErrorKind::UndefinedVariable { name: name.clone() }.into()),
+        }
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Object) -> Result<(), RuntimeError> {
+        if let Some(slot) = self.values.get_mut(&name.get_lexeme()) {
+            *slot = value;
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(ErrorKind::UndefinedVariable { name: name.clone() }.into()),
+        }
+    }
+}
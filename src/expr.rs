@@ -1,8 +1,8 @@
-use std::fmt;
+use std::{fmt, mem};
 
 use crate::token::{Literal, Token};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Unary {
         operator: Token,
@@ -19,6 +19,109 @@ pub enum Expr {
     Literal {
         literal: Literal,
     },
+    Variable {
+        name: Token,
+    },
+    Assign {
+        name: Token,
+        value: Box<Expr>,
+    },
+    Call {
+        callee: Box<Expr>,
+        /// The closing parenthesis, for reporting errors.
+        paren: Token,
+        arguments: Vec<Expr>,
+    },
+    Get {
+        object: Box<Expr>,
+        name: Token,
+    },
+    List {
+        elements: Vec<Expr>,
+    },
+    Map {
+        /// The closing brace, for reporting errors.
+        brace: Token,
+        entries: Vec<(Expr, Expr)>,
+    },
+    Index {
+        object: Box<Expr>,
+        /// The closing bracket, for reporting errors.
+        bracket: Token,
+        index: Box<Expr>,
+    },
+    SetIndex {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
+}
+
+impl Expr {
+    /// Moves the subexpressions of this one onto `stack`, leaving it a tree
+    /// of at most one level.
+    fn take_children(&mut self, stack: &mut Vec<Expr>) {
+        let mut take = |expr: &mut Box<Expr>| {
+            stack.push(mem::replace(
+                &mut **expr,
+                Expr::Literal {
+                    literal: Literal::Nil,
+                },
+            ))
+        };
+
+        match self {
+            Self::Literal { .. } | Self::Variable { .. } => {}
+            Self::Unary { right: expr, .. }
+            | Self::Grouping { expression: expr }
+            | Self::Assign { value: expr, .. }
+            | Self::Get { object: expr, .. } => take(expr),
+            Self::Binary { left, right, .. } => {
+                take(left);
+                take(right);
+            }
+            Self::Index { object, index, .. } => {
+                take(object);
+                take(index);
+            }
+            Self::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                take(object);
+                take(index);
+                take(value);
+            }
+            Self::Call {
+                callee, arguments, ..
+            } => {
+                take(callee);
+                stack.append(arguments);
+            }
+            Self::List { elements } => stack.append(elements),
+            Self::Map { entries, .. } => {
+                for (key, value) in entries.drain(..) {
+                    stack.push(key);
+                    stack.push(value);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Expr {
+    // Dropping a tree recursively could overflow the stack on one built by
+    // hand rather than by the parser, so it's taken apart a node at a time.
+    fn drop(&mut self) {
+        let mut stack = Vec::new();
+        self.take_children(&mut stack);
+        while let Some(mut expr) = stack.pop() {
+            expr.take_children(&mut stack);
+        }
+    }
 }
 
 impl fmt::Display for Expr {
@@ -32,6 +135,39 @@ impl fmt::Display for Expr {
             } => write!(f, "({} {} {})", operator.get_lexeme(), left, right),
             Self::Grouping { expression } => write!(f, "(group {})", expression),
             Self::Literal { literal } => write!(f, "{}", literal),
+            Self::Variable { name } => write!(f, "{}", name.get_lexeme()),
+            Self::Assign { name, value } => write!(f, "(= {} {})", name.get_lexeme(), value),
+            Self::Call {
+                callee, arguments, ..
+            } => {
+                write!(f, "(call {}", callee)?;
+                for argument in arguments {
+                    write!(f, " {}", argument)?;
+                }
+                write!(f, ")")
+            }
+            Self::Get { object, name } => write!(f, "(. {} {})", object, name.get_lexeme()),
+            Self::List { elements } => {
+                write!(f, "(list")?;
+                for element in elements {
+                    write!(f, " {}", element)?;
+                }
+                write!(f, ")")
+            }
+            Self::Map { entries, .. } => {
+                write!(f, "(map")?;
+                for (key, value) in entries {
+                    write!(f, " ({} {})", key, value)?;
+                }
+                write!(f, ")")
+            }
+            Self::Index { object, index, .. } => write!(f, "([] {} {})", object, index),
+            Self::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => write!(f, "([]= {} {} {})", object, index, value),
         }
     }
 }
@@ -0,0 +1,484 @@
+use std::ops::Range;
+
+use anyhow::Result;
+
+use crate::{lexer::Lexer, parser::Parser, token::Token, token_type::TokenType};
+
+const MAX_WIDTH: usize = 80;
+const INDENT: usize = 4;
+
+/// A code token along with what the formatter needs to know about where it
+/// sits in its statement.
+struct Atom {
+    text: String,
+    space_before: bool,
+    /// Parenthesis and bracket nesting depth of the token.
+    depth: usize,
+    /// For binary operators, how tightly the operator binds. Lines are only
+    /// ever broken before one of these.
+    precedence: Option<usize>,
+}
+
+/// An opinionated formatter for Lox source.
+///
+/// Every statement and brace gets a line of its own, indented by how deeply
+/// it is nested in blocks. Comments are kept where they were, at most one
+/// blank line is kept between statements, spacing is normalised, and
+/// statements that don't fit in `MAX_WIDTH` columns are broken before their
+/// loosest binding operators, with continuation lines indented.
+pub struct Formatter {
+    max_width: usize,
+}
+
+impl Formatter {
+    pub fn new() -> Self {
+        Self {
+            max_width: MAX_WIDTH,
+        }
+    }
+
+    pub fn format(&self, source: &str) -> Result<String> {
+        let tokens = Lexer::new(source).keep_comments().scan_tokens();
+
+        // Refuse to touch anything the parser rejects, so broken code is
+        // never rearranged into something that means something else.
+        let code: Vec<Token> = tokens
+            .iter()
+            .filter(|token| token.token_type != TokenType::Comment)
+            .cloned()
+            .collect();
+        Parser::new(code.clone()).parse()?;
+
+        let maps = self.map_braces(&code);
+        let atoms = self.atoms(&code, &maps);
+
+        let mut output = String::new();
+        // The atoms of the statement being collected.
+        let mut start = 0;
+        let mut end = 0;
+        // How deeply nested in blocks we are.
+        let mut level = 0;
+        // Whether part of the current statement was already written out
+        // because a comment interrupted it.
+        let mut continued = false;
+        let mut last_line = None;
+
+        for token in &tokens {
+            match token.token_type {
+                TokenType::Eof => break,
+                TokenType::Comment => {
+                    let trailing = last_line == Some(token.get_line());
+
+                    if end > start {
+                        let indent = self.indent(level, continued);
+                        self.write_lines(&mut output, &atoms, start..end, indent);
+                        // An `else` after a block isn't a continuation line.
+                        continued =
+                            code[end - 1].token_type != TokenType::RightBrace || maps[end - 1];
+                        start = end;
+                    }
+
+                    if trailing {
+                        output.pop();
+                        output.push(' ');
+                    } else {
+                        self.blank_line(&mut output, last_line, token.get_line());
+                        output.push_str(&" ".repeat(self.indent(level, continued)));
+                    }
+
+                    output.push_str(token.get_lexeme().trim_end());
+                    output.push('\n');
+                }
+                // `else` carries on from the brace closing the then branch.
+                TokenType::RightBrace
+                    if !maps[end]
+                        && code.get(end + 1).map(|token| &token.token_type)
+                            == Some(&TokenType::Else) =>
+                {
+                    level -= 1;
+                    if end == start && !continued {
+                        self.blank_line(&mut output, last_line, token.get_line());
+                    }
+                    end += 1;
+                }
+                TokenType::RightBrace if !maps[end] => {
+                    level -= 1;
+                    self.write_lines(&mut output, &atoms, end..end + 1, level * INDENT);
+                    end += 1;
+                    start = end;
+                    continued = false;
+                }
+                _ => {
+                    if end == start && !continued {
+                        self.blank_line(&mut output, last_line, token.get_line());
+                    }
+                    let map = maps[end];
+                    end += 1;
+
+                    if token.token_type == TokenType::Semicolon
+                        || token.token_type == TokenType::LeftBrace && !map
+                    {
+                        // A comment can leave a block's brace on a line of
+                        // its own, where it lines up with its statement.
+                        let alone = token.token_type == TokenType::LeftBrace && end - start == 1;
+                        let indent = self.indent(level, continued && !alone);
+                        self.write_lines(&mut output, &atoms, start..end, indent);
+                        start = end;
+                        continued = false;
+
+                        if token.token_type == TokenType::LeftBrace {
+                            level += 1;
+                        }
+                    }
+                }
+            }
+
+            last_line = Some(token.get_line());
+        }
+
+        let indent = self.indent(level, continued);
+        self.write_lines(&mut output, &atoms, start..end, indent);
+
+        Ok(output)
+    }
+
+    fn indent(&self, level: usize, continued: bool) -> usize {
+        level * INDENT + if continued { INDENT } else { 0 }
+    }
+
+    /// Keeps a single blank line where the source had one or more, except
+    /// straight after an opening brace.
+    fn blank_line(&self, output: &mut String, last_line: Option<usize>, line: usize) {
+        if let Some(last_line) = last_line {
+            if line > last_line + 1 && !output.ends_with("{\n") {
+                output.push('\n');
+            }
+        }
+    }
+
+    /// Which of the tokens are braces around a map rather than a block. A
+    /// brace opens a block where a statement can start, unless it's
+    /// followed by a simple key and a colon, as in the parser.
+    fn map_braces(&self, code: &[Token]) -> Vec<bool> {
+        let mut maps = vec![false; code.len()];
+        let mut open = Vec::new();
+
+        for (i, token) in code.iter().enumerate() {
+            match token.token_type {
+                TokenType::LeftBrace => {
+                    let statement_start = match i.checked_sub(1) {
+                        None => true,
+                        Some(previous) => match code[previous].token_type {
+                            TokenType::LeftBrace => !maps[previous],
+                            TokenType::Semicolon
+                            | TokenType::RightBrace
+                            | TokenType::RightParen
+                            | TokenType::Else => true,
+                            _ => false,
+                        },
+                    };
+                    let token_type =
+                        |offset| code.get(i + offset).map(|token: &Token| &token.token_type);
+                    let keyed = matches!(
+                        token_type(1),
+                        Some(
+                            TokenType::String
+                                | TokenType::Number
+                                | TokenType::Identifier
+                                | TokenType::True
+                                | TokenType::False
+                                | TokenType::Nil
+                        )
+                    ) && token_type(2) == Some(&TokenType::Colon);
+
+                    maps[i] = !statement_start || keyed;
+                    open.push(maps[i]);
+                }
+                TokenType::RightBrace => maps[i] = open.pop().unwrap_or(false),
+                _ => {}
+            }
+        }
+
+        maps
+    }
+
+    fn atoms(&self, code: &[Token], maps: &[bool]) -> Vec<Atom> {
+        let mut atoms = Vec::new();
+        let mut depth = 0;
+        let mut previous: Option<(TokenType, bool)> = None;
+
+        for (i, token) in code.iter().enumerate() {
+            if token.token_type == TokenType::Eof {
+                break;
+            }
+
+            // The braces of a map are spaced like brackets.
+            let token_type = match token.token_type {
+                TokenType::LeftBrace if maps[i] => TokenType::LeftBracket,
+                TokenType::RightBrace if maps[i] => TokenType::RightBracket,
+                ref token_type => token_type.clone(),
+            };
+
+            let follows_operand = matches!(
+                previous,
+                Some((
+                    TokenType::RightParen
+                        | TokenType::RightBracket
+                        | TokenType::Number
+                        | TokenType::String
+                        | TokenType::Identifier
+                        | TokenType::True
+                        | TokenType::False
+                        | TokenType::Nil,
+                    _
+                ))
+            );
+
+            if matches!(token_type, TokenType::RightParen | TokenType::RightBracket) {
+                depth -= 1;
+            }
+
+            let unary =
+                !follows_operand && matches!(token_type, TokenType::Minus | TokenType::Bang);
+            // A parenthesis or bracket straight after an operand opens a
+            // call or an index.
+            let postfix = follows_operand
+                && matches!(token_type, TokenType::LeftParen | TokenType::LeftBracket);
+            let space_before = match previous {
+                None => false,
+                Some((TokenType::LeftParen | TokenType::LeftBracket | TokenType::Dot, _)) => false,
+                Some((_, previous_unary)) if previous_unary => false,
+                _ => {
+                    !postfix
+                        && !matches!(
+                            token_type,
+                            TokenType::RightParen
+                                | TokenType::RightBracket
+                                | TokenType::Semicolon
+                                | TokenType::Comma
+                                | TokenType::Colon
+                                | TokenType::Dot
+                        )
+                }
+            };
+            let precedence = if unary {
+                None
+            } else {
+                self.precedence(&token_type)
+            };
+
+            atoms.push(Atom {
+                text: token.get_lexeme(),
+                space_before,
+                depth,
+                precedence,
+            });
+
+            if matches!(token_type, TokenType::LeftParen | TokenType::LeftBracket) {
+                depth += 1;
+            }
+            previous = Some((token_type, unary));
+        }
+
+        atoms
+    }
+
+    fn precedence(&self, token_type: &TokenType) -> Option<usize> {
+        match token_type {
+            TokenType::BangEqual | TokenType::EqualEqual => Some(0),
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => Some(1),
+            TokenType::Minus | TokenType::Plus => Some(2),
+            TokenType::Slash | TokenType::Star | TokenType::Percent => Some(3),
+            _ => None,
+        }
+    }
+
+    fn render(&self, atoms: &[Atom]) -> String {
+        let mut line = String::new();
+        for (i, atom) in atoms.iter().enumerate() {
+            if i > 0 && atom.space_before {
+                line.push(' ');
+            }
+            line.push_str(&atom.text);
+        }
+        line
+    }
+
+    /// Writes the atoms in `range` starting at `indent`, splitting them over
+    /// several lines if they don't fit.
+    fn write_lines(&self, output: &mut String, atoms: &[Atom], range: Range<usize>, indent: usize) {
+        if range.is_empty() {
+            return;
+        }
+
+        let line = self.render(&atoms[range.clone()]);
+
+        // Break before the loosest binding operators at the shallowest depth.
+        let split = atoms[range.start + 1..range.end]
+            .iter()
+            .filter_map(|atom| atom.precedence.map(|precedence| (atom.depth, precedence)))
+            .min();
+
+        match split {
+            Some(split) if indent + line.len() > self.max_width => {
+                let mut start = range.start;
+                for i in range.start + 1..range.end {
+                    if atoms[i]
+                        .precedence
+                        .map(|precedence| (atoms[i].depth, precedence))
+                        == Some(split)
+                    {
+                        let indent = if start == range.start {
+                            indent
+                        } else {
+                            indent + INDENT
+                        };
+                        self.write_lines(output, atoms, start..i, indent);
+                        start = i;
+                    }
+                }
+
+                let indent = if start == range.start {
+                    indent
+                } else {
+                    indent + INDENT
+                };
+                self.write_lines(output, atoms, start..range.end, indent);
+            }
+            _ => {
+                output.push_str(&" ".repeat(indent));
+                output.push_str(&line);
+                output.push('\n');
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(source: &str) -> String {
+        Formatter::new().format(source).unwrap()
+    }
+
+    #[test]
+    fn spacing() {
+        assert_eq!(format("1+2 ;"), "1 + 2;\n");
+        assert_eq!(format("-  1 == ! true;"), "-1 == !true;\n");
+        assert_eq!(format("( 1+2 )*-( 3 );"), "(1 + 2) * -(3);\n");
+        assert_eq!(format("var  a=-1;print a;"), "var a = -1;\nprint a;\n");
+        assert_eq!(format("f ( 1 ,-2 ) ( );"), "f(1, -2)();\n");
+        assert_eq!(
+            format("var xs=[ 1,[ ] ];xs [0] = xs . len ( ) ;"),
+            "var xs = [1, []];\nxs[0] = xs.len();\n"
+        );
+        assert_eq!(
+            format("var m={ \"a\" :1,2:{ } };{a:m}[a];"),
+            "var m = {\"a\": 1, 2: {}};\n{a: m}[a];\n"
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        assert_eq!(
+            format("var a=1;{var b;\n\n\nprint a+b;\n{}}\n\n\na=2;"),
+            "var a = 1;\n{\n    var b;\n\n    print a + b;\n    {\n    }\n}\n\na = 2;\n"
+        );
+    }
+
+    #[test]
+    fn for_in() {
+        assert_eq!(
+            format("for(var x in range( 0,3 ))print x;"),
+            "for (var x in range(0, 3)) print x;\n"
+        );
+        assert_eq!(
+            format("for (var k in {a: 1}) {print k;}"),
+            "for (var k in {a: 1}) {\n    print k;\n}\n"
+        );
+    }
+
+    #[test]
+    fn if_while() {
+        assert_eq!(
+            format("if(a){print 1;}else if(b)print 2;else{print 3;}"),
+            "if (a) {\n    print 1;\n} else if (b) print 2;\nelse {\n    print 3;\n}\n"
+        );
+        assert_eq!(
+            format("if (a) {}\n// b\nelse {}"),
+            "if (a) {\n}\n// b\nelse {\n}\n"
+        );
+        assert_eq!(
+            format("while(i<3){i=i+1;}"),
+            "while (i < 3) {\n    i = i + 1;\n}\n"
+        );
+    }
+
+    #[test]
+    fn maps_in_blocks() {
+        assert_eq!(
+            format("{{}\n{ print {}; }}"),
+            "{\n    {\n    }\n    {\n        print {};\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(
+            format("// header\n1 +   2;   // trailing\n"),
+            "// header\n1 + 2; // trailing\n"
+        );
+        assert_eq!(format("1 + // one\n2;"), "1 + // one\n    2;\n");
+        assert_eq!(
+            format("1 +\n// own line\n2;"),
+            "1 +\n    // own line\n    2;\n"
+        );
+        assert_eq!(format("nil;\n// footer"), "nil;\n// footer\n");
+        assert_eq!(
+            format("{ // open\n// inside\nnil;\n} // close"),
+            "{ // open\n    // inside\n    nil;\n} // close\n"
+        );
+        assert_eq!(
+            format("for (var x in xs) // c\n{ print x; }"),
+            "for (var x in xs) // c\n{\n    print x;\n}\n"
+        );
+        assert_eq!(
+            format("while (a) {\nif (b) // c\n{ print b; } }"),
+            "while (a) {\n    if (b) // c\n    {\n        print b;\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn wraps_long_lines() {
+        let formatter = Formatter { max_width: 25 };
+        assert_eq!(
+            formatter
+                .format("111111 + 222222 * 333333 + 444444;")
+                .unwrap(),
+            "111111\n    + 222222 * 333333\n    + 444444;\n"
+        );
+        assert_eq!(
+            formatter
+                .format("{ (111111 + 222222) == (3 - 4); }")
+                .unwrap(),
+            "{\n    (111111 + 222222)\n        == (3 - 4);\n}\n"
+        );
+    }
+
+    #[test]
+    fn idempotent() {
+        let formatter = Formatter { max_width: 20 };
+        let source = "// a\nvar x=1+2*3 - (4/5 +6) // b\n== 7+8+9+10+11+12;{{print x;}\n\n// c\n}";
+        let once = formatter.format(source).unwrap();
+        assert_eq!(formatter.format(&once).unwrap(), once);
+    }
+
+    #[test]
+    fn rejects_invalid_source() {
+        assert!(Formatter::new().format("1 +;").is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! Proptest strategies that generate syntax trees for random but valid Lox
+//! programs, for testing passes against each other.
+
+use proptest::prelude::*;
+
+use crate::{
+    expr::Expr,
+    lexer::Lexer,
+    stmt::Stmt,
+    token::{Literal, Token},
+    token_type::TokenType,
+};
+
+fn token(token_type: TokenType, lexeme: &str) -> Token {
+    Token::new(token_type, lexeme.to_owned(), None, 1)
+}
+
+/// Any identifier that isn't a keyword.
+pub fn name() -> BoxedStrategy<Token> {
+    "[a-z][a-z0-9]{0,4}"
+        .prop_filter("keywords aren't names", |name| {
+            Lexer::new(name).scan_tokens()[0].token_type == TokenType::Identifier
+        })
+        .prop_map(|name| token(TokenType::Identifier, &name))
+        .boxed()
+}
+
+/// One of a handful of names, so that programs keep referring back to the
+/// same variables.
+fn variable() -> BoxedStrategy<Token> {
+    prop::sample::select(vec!["a", "b", "c"])
+        .prop_map(|name| token(TokenType::Identifier, name))
+        .boxed()
+}
+
+/// A few method names, some of which exist.
+fn method() -> BoxedStrategy<Token> {
+    prop::sample::select(vec!["len", "pop", "push", "has", "keys", "nope"])
+        .prop_map(|name| token(TokenType::Identifier, name))
+        .boxed()
+}
+
+pub fn expr() -> BoxedStrategy<Expr> {
+    expr_with(name())
+}
+
+fn expr_with(names: BoxedStrategy<Token>) -> BoxedStrategy<Expr> {
+    let literal = prop_oneof![
+        (0u32..10_000).prop_map(|n| Literal::Number(n as f32 / 100.0)),
+        "[a-zA-Z0-9 ]{0,8}".prop_map(|string| Literal::String(string.into())),
+        any::<bool>().prop_map(Literal::Bool),
+        Just(Literal::Nil),
+    ]
+    .prop_map(|literal| Expr::Literal { literal });
+    let leaf = prop_oneof![
+        literal,
+        names.clone().prop_map(|name| Expr::Variable { name })
+    ];
+
+    leaf.prop_recursive(6, 64, 2, move |inner| {
+        let unary = prop_oneof![
+            Just(token(TokenType::Minus, "-")),
+            Just(token(TokenType::Bang, "!")),
+        ];
+        let binary = prop_oneof![
+            Just(token(TokenType::BangEqual, "!=")),
+            Just(token(TokenType::EqualEqual, "==")),
+            Just(token(TokenType::Greater, ">")),
+            Just(token(TokenType::GreaterEqual, ">=")),
+            Just(token(TokenType::Less, "<")),
+            Just(token(TokenType::LessEqual, "<=")),
+            Just(token(TokenType::Minus, "-")),
+            Just(token(TokenType::Plus, "+")),
+            Just(token(TokenType::Slash, "/")),
+            Just(token(TokenType::Star, "*")),
+            Just(token(TokenType::Percent, "%")),
+        ];
+
+        prop_oneof![
+            (unary, inner.clone()).prop_map(|(operator, right)| Expr::Unary {
+                operator,
+                right: Box::new(right),
+            }),
+            (inner.clone(), binary, inner.clone()).prop_map(|(left, operator, right)| {
+                Expr::Binary {
+                    left: Box::new(left),
+                    operator,
+                    right: Box::new(right),
+                }
+            }),
+            (names.clone(), inner.clone()).prop_map(|(name, value)| Expr::Assign {
+                name,
+                value: Box::new(value),
+            }),
+            (inner.clone(), prop::collection::vec(inner.clone(), 0..3)).prop_map(
+                |(callee, arguments)| Expr::Call {
+                    callee: Box::new(callee),
+                    paren: token(TokenType::RightParen, ")"),
+                    arguments,
+                }
+            ),
+            (inner.clone(), method()).prop_map(|(object, name)| Expr::Get {
+                object: Box::new(object),
+                name,
+            }),
+            prop::collection::vec(inner.clone(), 0..3).prop_map(|elements| Expr::List { elements }),
+            prop::collection::vec((inner.clone(), inner.clone()), 0..3).prop_map(|entries| {
+                Expr::Map {
+                    brace: token(TokenType::RightBrace, "}"),
+                    entries,
+                }
+            }),
+            (inner.clone(), inner.clone()).prop_map(|(object, index)| Expr::Index {
+                object: Box::new(object),
+                bracket: token(TokenType::RightBracket, "]"),
+                index: Box::new(index),
+            }),
+            (inner.clone(), inner.clone(), inner.clone()).prop_map(|(object, index, value)| {
+                Expr::SetIndex {
+                    object: Box::new(object),
+                    bracket: token(TokenType::RightBracket, "]"),
+                    index: Box::new(index),
+                    value: Box::new(value),
+                }
+            }),
+            inner.prop_map(|expression| Expr::Grouping {
+                expression: Box::new(expression),
+            }),
+        ]
+    })
+    .boxed()
+}
+
+/// A whole program, using a few variables that may or may not have been
+/// declared by the time they're used.
+pub fn program() -> BoxedStrategy<Vec<Stmt>> {
+    let expr = expr_with(variable());
+    let iterable = expr.clone();
+    let condition = expr.clone();
+    let leaf = prop_oneof![
+        expr.clone()
+            .prop_map(|expression| Stmt::Expression { expression }),
+        expr.clone()
+            .prop_map(|expression| Stmt::Print { expression }),
+        (variable(), prop::option::of(expr))
+            .prop_map(|(name, initializer)| Stmt::Var { name, initializer }),
+    ];
+
+    let statement = leaf.prop_recursive(3, 32, 4, move |inner| {
+        let block =
+            prop::collection::vec(inner, 0..4).prop_map(|statements| Stmt::Block { statements });
+        prop_oneof![
+            block.clone(),
+            // Only blocks are generated as bodies, as a declaration on its
+            // own isn't allowed there. There are no `while` loops, as most
+            // would never finish.
+            (variable(), iterable.clone(), block.clone()).prop_map(|(name, iterable, body)| {
+                Stmt::ForIn {
+                    name,
+                    iterable,
+                    body: Box::new(body),
+                }
+            }),
+            (condition.clone(), block.clone(), prop::option::of(block)).prop_map(
+                |(condition, then_branch, else_branch)| Stmt::If {
+                    condition,
+                    then_branch: Box::new(then_branch),
+                    else_branch: else_branch.map(Box::new),
+                }
+            ),
+        ]
+    });
+
+    prop::collection::vec(statement, 0..8).boxed()
+}
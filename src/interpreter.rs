@@ -1,71 +1,530 @@
-// use std::fmt;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt, fs,
+    io::{self, BufRead, BufReader, Write},
+    mem,
+    path::PathBuf,
+    rc::Rc,
+    slice,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 
 use crate::{
+    capabilities::Capabilities,
+    environment::Environment,
     expr::Expr,
+    lexer::Lexer,
+    natives,
+    object::{Key, Method, Module, Object},
+    parser::Parser,
+    stmt::Stmt,
     token::{Literal, Token},
     token_type::TokenType,
 };
 
 use anyhow::Result;
-// use lazy_static::lazy_static;
+use indexmap::IndexMap;
 use thiserror::Error;
 
-// lazy_static! {
-//     pub static ref INTERPRETER: Interpreter = {
-//         let interpreter = Interpreter::new();
-//         interpreter
-//     };
-// }
-
-// #[derive(Debug)]
-// enum Object {
-//     String(String),
-//     Number(f32),
-//     Bool(bool),
-//     Nil,
-// }
-
-// impl fmt::Display for Object {
-//     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-//         match self {
-//             Self::String(ref string) => write!(f, "{}", string),
-//             Self::Number(num) => write!(f, "{}", num),
-//             Self::Bool(bool) => write!(f, "{}", bool),
-//             Self::Nil => write!(f, "nil"),
-//         }
-//     }
-// }
-
-pub struct Interpreter {}
+/// How deeply expressions and blocks may nest by default.
+const MAX_DEPTH: usize = 1000;
+/// Nesting moves onto a new stack segment when less than `RED_ZONE` bytes of
+/// the native stack are left, so `max_depth` is the only limit, whatever
+/// stack the interpreter happens to be running on.
+pub(crate) const RED_ZONE: usize = 64 * 1024;
+pub(crate) const STACK_SEGMENT: usize = 1024 * 1024;
+
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    /// The script being run, for stack traces and for finding what it
+    /// imports.
+    file: Option<PathBuf>,
+    /// Modules that have been run, by canonical path, so each is only run
+    /// once.
+    modules: HashMap<PathBuf, Object>,
+    /// The canonical paths of the files part way through an import, outermost
+    /// first, for catching cycles.
+    importing: Vec<PathBuf>,
+    /// The line most recently reached in the current function.
+    line: Cell<usize>,
+    /// How many more steps may be taken, if there's a limit.
+    fuel: Cell<Option<u64>>,
+    deadline: Option<Instant>,
+    /// How deeply nested the expression or block being run is.
+    depth: Cell<usize>,
+    max_depth: usize,
+    /// Bytes of strings, lists and maps created so far.
+    allocated: Cell<usize>,
+    max_allocated: Option<usize>,
+    pub(crate) capabilities: Capabilities,
+    pub(crate) started: Instant,
+    /// The state of the generator behind the `random` native.
+    pub(crate) random: Cell<u64>,
+    /// What the `args` native gives the program.
+    pub(crate) args: Vec<String>,
+    /// Where `readLine` reads from.
+    pub(crate) input: RefCell<Box<dyn BufRead>>,
+    /// Where `print` writes to.
+    output: RefCell<Box<dyn Write>>,
+    /// Where errors are reported.
+    errors: RefCell<Box<dyn Write>>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Interpreter {
     pub fn new() -> Self {
-        Self {}
+        let globals = Self::global_environment();
+
+        Self {
+            environment: globals.clone(),
+            globals,
+            file: None,
+            modules: HashMap::new(),
+            importing: Vec::new(),
+            line: Cell::new(1),
+            fuel: Cell::new(None),
+            deadline: None,
+            depth: Cell::new(0),
+            max_depth: MAX_DEPTH,
+            allocated: Cell::new(0),
+            max_allocated: None,
+            capabilities: Capabilities::all(),
+            started: Instant::now(),
+            random: Cell::new(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |time| time.as_nanos() as u64),
+            ),
+            args: Vec::new(),
+            input: RefCell::new(Box::new(BufReader::new(io::stdin()))),
+            output: RefCell::new(Box::new(io::stdout())),
+            errors: RefCell::new(Box::new(io::stderr())),
+        }
+    }
+
+    /// Give the program these arguments, such as the ones after the script
+    /// on the command line.
+    pub fn args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Have the program read lines from here rather than from stdin.
+    pub fn input(mut self, input: impl BufRead + 'static) -> Self {
+        self.input = RefCell::new(Box::new(input));
+        self
+    }
+
+    /// Send what the program prints here rather than to stdout.
+    pub fn output(mut self, output: impl Write + 'static) -> Self {
+        self.output = RefCell::new(Box::new(output));
+        self
+    }
+
+    /// Send errors passed to `report` here rather than to stderr.
+    pub fn errors(mut self, errors: impl Write + 'static) -> Self {
+        self.errors = RefCell::new(Box::new(errors));
+        self
+    }
+
+    fn global_environment() -> Rc<RefCell<Environment>> {
+        let mut environment = Environment::new();
+        natives::define(&mut environment);
+        Rc::new(RefCell::new(environment))
+    }
+
+    /// Forgets everything the program has defined and allocated, keeping
+    /// the configuration and where output goes.
+    pub fn reset(&mut self) {
+        self.globals = Self::global_environment();
+        self.environment = self.globals.clone();
+        self.modules.clear();
+        self.allocated.set(0);
+    }
+
+    /// Limit what the program may do outside the interpreter. Everything is
+    /// allowed by default.
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Start the `random` native from `seed`, rather than from the time, so
+    /// the numbers it gives can be repeated.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.random = Cell::new(seed);
+        self
+    }
+
+    /// Raise a `Stack overflow.` error beyond this depth of nesting, rather
+    /// than overflowing the native stack.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Raise an `Out of memory.` error once the strings, lists and maps
+    /// created by the program add up to more than this many bytes.
+    ///
+    /// This is an allocation budget, not a cap on live memory: nothing is
+    /// given back when a value is dropped, so a long-running program that
+    /// keeps making small values will run out in the end. Since it covers
+    /// everything that is live as well, it still bounds how much memory the
+    /// program can hold on to.
+    pub fn max_allocated(mut self, max_allocated: usize) -> Self {
+        self.max_allocated = Some(max_allocated);
+        self
+    }
+
+    /// Stop with `ErrorKind::OutOfFuel` after executing this many statements
+    /// and expressions.
+    pub fn fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Cell::new(Some(fuel));
+        self
+    }
+
+    /// Stop with `ErrorKind::DeadlineExceeded` if still running at
+    /// `deadline`.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Replace both limits, e.g. to give each of a series of programs run
+    /// in the same interpreter a fresh budget.
+    pub fn set_budget(&mut self, fuel: Option<u64>, deadline: Option<Instant>) {
+        self.fuel.set(fuel);
+        self.deadline = deadline;
+    }
+
+    /// Name the file the program came from in stack traces.
+    pub fn file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Everything defined in the global scope, sorted by name.
+    pub(crate) fn globals(&self) -> Vec<(String, Object)> {
+        let mut globals = self.globals.borrow().values();
+        globals.sort_by(|(a, _), (b, _)| a.cmp(b));
+        globals
+    }
+
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), RuntimeError> {
+        statements
+            .iter()
+            .try_for_each(|statement| self.execute(statement))
+            .map_err(|error| self.unwind(error, "script"))
+    }
+
+    /// Runs a program from `file`, finding its imports relative to it and
+    /// naming it in stack traces, then goes back to the file set before.
+    pub(crate) fn interpret_file(
+        &mut self,
+        statements: &[Stmt],
+        file: PathBuf,
+    ) -> Result<(), RuntimeError> {
+        let previous_file = self.file.replace(file);
+        let result = self.interpret(statements);
+        self.file = previous_file;
+        result
+    }
+
+    /// Adds the frame of the function being left to the stack of an error
+    /// that is escaping from it.
+    fn unwind(&self, mut error: RuntimeError, function: &str) -> RuntimeError {
+        error.stack.push(Frame {
+            function: function.to_owned(),
+            file: self.file.clone(),
+            line: self.line.get(),
+        });
+        error
+    }
+
+    /// Accounts for one step of execution, failing if that goes over
+    /// budget.
+    fn step(&self) -> Result<(), RuntimeError> {
+        if let Some(fuel) = self.fuel.get() {
+            if fuel == 0 {
+                return Err(ErrorKind::OutOfFuel.into());
+            }
+            self.fuel.set(Some(fuel - 1));
+        }
+
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                return Err(ErrorKind::DeadlineExceeded.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn enter(&self) -> Result<(), RuntimeError> {
+        if self.depth.get() >= self.max_depth {
+            return Err(ErrorKind::StackOverflow.into());
+        }
+        self.depth.set(self.depth.get() + 1);
+        Ok(())
+    }
+
+    fn leave(&self) {
+        self.depth.set(self.depth.get() - 1);
+    }
+
+    /// Accounts for a new string of `bytes` bytes.
+    pub(crate) fn allocate(&self, bytes: usize) -> Result<(), RuntimeError> {
+        let allocated = self.allocated.get().saturating_add(bytes);
+        if self
+            .max_allocated
+            .is_some_and(|max_allocated| allocated > max_allocated)
+        {
+            return Err(ErrorKind::OutOfMemory.into());
+        }
+        self.allocated.set(allocated);
+        Ok(())
+    }
+
+    /// Writes a value to the output on a line of its own, if the program is
+    /// allowed to.
+    pub(crate) fn print(&self, value: &Object) -> Result<(), RuntimeError> {
+        self.require(self.capabilities.stdout, "write to stdout")?;
+        self.write(&format!("{}\n", value))
+    }
+
+    /// Writes text to the output as it is, whatever the program is allowed
+    /// to do. For output that comes from the host rather than the program,
+    /// such as the REPL's commands.
+    pub(crate) fn write(&self, text: &str) -> Result<(), RuntimeError> {
+        let mut output = self.output.borrow_mut();
+        output
+            .write_all(text.as_bytes())
+            .and_then(|_| output.flush())
+            .map_err(|error| ErrorKind::Output(error).into())
+    }
+
+    /// Writes a message, such as the trace of an error, to the error sink.
+    /// Failing to do so is ignored, as there's nowhere left to report it.
+    pub fn report(&self, message: &str) {
+        let _ = writeln!(self.errors.borrow_mut(), "{}", message);
     }
 
-    pub fn interpret(&mut self, expr: &Expr) -> Result<(), RuntimeError> {
-        let value = self.evaluate(expr)?;
-        println!("{:?}", value);
+    /// Fails with `ErrorKind::PermissionDenied` unless `allowed`, naming the
+    /// `action` that was refused.
+    pub(crate) fn require(&self, allowed: bool, action: &str) -> Result<(), RuntimeError> {
+        if allowed {
+            return Ok(());
+        }
+
+        Err(ErrorKind::PermissionDenied {
+            action: action.to_owned(),
+        }
+        .into())
+    }
+
+    pub(crate) fn execute(&mut self, stmt: &Stmt) -> Result<(), RuntimeError> {
+        self.step()?;
+
+        match stmt {
+            Stmt::Expression { expression } => {
+                self.evaluate(expression)?;
+            }
+            Stmt::Print { expression } => {
+                let value = self.evaluate(expression)?;
+                self.print(&value)?;
+            }
+            Stmt::Var { name, initializer } => {
+                let value = match initializer {
+                    Some(initializer) => self.evaluate(initializer)?,
+                    None => Object::Nil,
+                };
+                self.environment
+                    .borrow_mut()
+                    .define(name.get_lexeme(), value);
+            }
+            Stmt::Block { statements } => {
+                let environment = Environment::new_enclosing(self.environment.clone());
+                self.execute_block(statements, environment)?;
+            }
+            Stmt::Import { path, name } => {
+                let module = self.import(path)?;
+                self.environment
+                    .borrow_mut()
+                    .define(name.get_lexeme(), module);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = self.evaluate(condition)?;
+                if self.is_truthy(condition) {
+                    self.execute_nested(then_branch)?;
+                } else if let Some(else_branch) = else_branch {
+                    self.execute_nested(else_branch)?;
+                }
+            }
+            Stmt::While { condition, body } => loop {
+                let value = self.evaluate(condition)?;
+                if !self.is_truthy(value) {
+                    break;
+                }
+                self.execute_nested(body)?;
+            },
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => {
+                let iterable = self.evaluate(iterable)?;
+                self.line.set(name.get_line());
+
+                for element in self.elements(&iterable)? {
+                    let mut environment = Environment::new_enclosing(self.environment.clone());
+                    environment.define(name.get_lexeme(), element);
+                    self.execute_block(slice::from_ref(body), environment)?;
+                }
+            }
+        }
+
         Ok(())
     }
 
-    fn evaluate(&self, expr: &Expr) -> Result<Literal, RuntimeError> {
+    /// Runs a statement that's part of another, such as the body of a loop,
+    /// in the same environment.
+    fn execute_nested(&mut self, stmt: &Stmt) -> Result<(), RuntimeError> {
+        self.enter()?;
+        let result = stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || self.execute(stmt));
+        self.leave();
+
+        result
+    }
+
+    fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Environment,
+    ) -> Result<(), RuntimeError> {
+        self.enter()?;
+        let previous = mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || {
+            statements
+                .iter()
+                .try_for_each(|statement| self.execute(statement))
+        });
+        self.environment = previous;
+        self.leave();
+
+        result
+    }
+
+    /// The module at `path`, relative to the file being run, running it
+    /// first unless it already has been.
+    fn import(&mut self, path: &Token) -> Result<Object, RuntimeError> {
+        self.line.set(path.get_line());
+        let name = match &path.literal {
+            Some(Literal::String(name)) => name.to_string(),
+            _ => unreachable!("import path is a string literal"),
+        };
+        let file = match self.file.as_ref().and_then(|file| file.parent()) {
+            Some(directory) => directory.join(&name),
+            None => PathBuf::from(&name),
+        };
+        self.require(
+            self.capabilities.can_read(&file),
+            &format!("read '{}'", name),
+        )?;
+
+        let io_error = |error| ErrorKind::Io {
+            action: "import",
+            path: name.clone(),
+            error,
+        };
+        let canonical = file.canonicalize().map_err(io_error)?;
+        if let Some(module) = self.modules.get(&canonical) {
+            return Ok(module.clone());
+        }
+        let current = self.file.as_ref().and_then(|file| file.canonicalize().ok());
+        if current.as_ref() == Some(&canonical) || self.importing.contains(&canonical) {
+            return Err(ErrorKind::ImportCycle { path: name }.into());
+        }
+
+        let source = fs::read_to_string(&file).map_err(io_error)?;
+        let statements = Parser::new(Lexer::new(&source).scan_tokens())
+            .parse()
+            .map_err(|error| ErrorKind::ImportSyntax {
+                path: name.clone(),
+                message: error.to_string(),
+            })?;
+
+        // The module gets globals of its own, so it only sees the natives
+        // and what it defines itself.
+        let globals = Rc::new(RefCell::new(Environment::new_enclosing(
+            Self::global_environment(),
+        )));
+        self.enter()?;
+        let importing = self.importing.len();
+        self.importing.extend(current);
+        let previous_globals = mem::replace(&mut self.globals, globals.clone());
+        let previous_environment = mem::replace(&mut self.environment, globals.clone());
+        let previous_file = self.file.replace(file);
+        let previous_line = self.line.replace(1);
+        let result = stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || {
+            statements
+                .iter()
+                .try_for_each(|statement| self.execute(statement))
+                .map_err(|error| self.unwind(error, "script"))
+        });
+        self.globals = previous_globals;
+        self.environment = previous_environment;
+        self.file = previous_file;
+        self.line.set(previous_line);
+        self.importing.truncate(importing);
+        self.leave();
+        result?;
+
+        let module = Object::Module(Rc::new(Module {
+            path: name,
+            globals,
+        }));
+        self.modules.insert(canonical, module.clone());
+        Ok(module)
+    }
+
+    pub(crate) fn evaluate(&self, expr: &Expr) -> Result<Object, RuntimeError> {
+        self.step()?;
+        self.enter()?;
+        let result = stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || self.expression(expr));
+        self.leave();
+
+        result
+    }
+
+    fn expression(&self, expr: &Expr) -> Result<Object, RuntimeError> {
         match expr {
-            Expr::Literal { literal } => Ok(literal.clone()),
+            Expr::Literal { literal } => Ok(literal.clone().into()),
             Expr::Grouping { expression } => self.evaluate(expression),
+            Expr::Variable { name } => {
+                self.line.set(name.get_line());
+                self.environment.borrow().get(name)
+            }
+            Expr::Assign { name, value } => {
+                let value = self.evaluate(value)?;
+                self.line.set(name.get_line());
+                self.environment.borrow_mut().assign(name, value.clone())?;
+                Ok(value)
+            }
             Expr::Unary { operator, right } => {
                 let right = self.evaluate(right)?;
-
-                match operator.token_type {
-                    TokenType::Minus => {
-                        let number = self.check_number_operand(&operator, &right)?;
-                        Ok(Literal::Number(-number))
-                    }
-                    TokenType::Bang => Ok(Literal::Bool(!self.is_truthy(right))),
-                    _ => {
-                        unreachable!()
-                    }
-                }
+                self.line.set(operator.get_line());
+                self.unary(operator, right)
             }
             Expr::Binary {
                 left,
@@ -74,109 +533,780 @@ impl Interpreter {
             } => {
                 let left = self.evaluate(left)?;
                 let right = self.evaluate(right)?;
+                self.line.set(operator.get_line());
+                self.binary(operator, left, right)
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                let callee = self.evaluate(callee)?;
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.evaluate(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.line.set(paren.get_line());
+                self.call(callee, &arguments)
+            }
+            Expr::Get { object, name } => {
+                let object = self.evaluate(object)?;
+                self.line.set(name.get_line());
+                self.get(object, name)
+            }
+            Expr::List { elements } => {
+                let elements = elements
+                    .iter()
+                    .map(|element| self.evaluate(element))
+                    .collect::<Result<Vec<_>, _>>()?;
+                natives::new_list(self, elements)
+            }
+            Expr::Map { brace, entries } => {
+                let mut map = IndexMap::new();
+                for (key, value) in entries {
+                    let key = self.evaluate(key)?;
+                    let value = self.evaluate(value)?;
+                    self.line.set(brace.get_line());
+                    map.insert(natives::key(&key)?, value);
+                }
+                self.allocate(map.len() * natives::ENTRY_SIZE)?;
+                Ok(Object::map(map))
+            }
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                self.line.set(bracket.get_line());
+                self.index(&object, &index)
+            }
+            Expr::SetIndex {
+                object,
+                bracket,
+                index,
+                value,
+            } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                let value = self.evaluate(value)?;
+                self.line.set(bracket.get_line());
+                self.set_index(&object, &index, value.clone())?;
+                Ok(value)
+            }
+        }
+    }
 
-                match operator.token_type {
-                    TokenType::Minus => {
-                        let (left, right) = self.check_number_operands(&operator, &left, &right)?;
-                        return Ok(Literal::Number(left - right));
-                    }
-                    TokenType::Slash => {
-                        let (left, right) = self.check_number_operands(&operator, &left, &right)?;
-                        return Ok(Literal::Number(left / right));
-                    }
-                    TokenType::Star => {
-                        let (left, right) = self.check_number_operands(&operator, &left, &right)?;
-                        return Ok(Literal::Number(left * right));
-                    }
-                    TokenType::Plus => {
-                        if let Ok((left, right)) =
-                            self.check_number_operands(&operator, &left, &right)
-                        {
-                            return Ok(Literal::Number(left + right));
-                        }
-
-                        if let (Literal::String(left), Literal::String(right)) = (left, right) {
-                            return Ok(Literal::String(left.clone() + &right));
-                        }
-
-                        Err(RuntimeError::InvalidOperands {
-                            operator: operator.clone(),
-                        })
-                    }
-                    TokenType::Greater => {
-                        let (left, right) = self.check_number_operands(&operator, &left, &right)?;
-                        return Ok(Literal::Bool(left > right));
-                    }
-                    TokenType::GreaterEqual => {
-                        let (left, right) = self.check_number_operands(&operator, &left, &right)?;
-                        return Ok(Literal::Bool(left >= right));
-                    }
-                    TokenType::Less => {
-                        let (left, right) = self.check_number_operands(&operator, &left, &right)?;
-                        return Ok(Literal::Bool(left < right));
-                    }
-                    TokenType::LessEqual => {
-                        let (left, right) = self.check_number_operands(&operator, &left, &right)?;
-                        return Ok(Literal::Bool(left <= right));
+    // Applying operators is kept out of `expression`, so that the frames
+    // left on the native stack while evaluating nested operands stay small.
+
+    fn unary(&self, operator: &Token, right: Object) -> Result<Object, RuntimeError> {
+        match operator.token_type {
+            TokenType::Minus => {
+                let number = self.check_number_operand(operator, &right)?;
+                Ok(Object::Number(-number))
+            }
+            TokenType::Bang => Ok(Object::Bool(!self.is_truthy(right))),
+            _ => unreachable!(),
+        }
+    }
+
+    fn binary(
+        &self,
+        operator: &Token,
+        left: Object,
+        right: Object,
+    ) -> Result<Object, RuntimeError> {
+        match operator.token_type {
+            TokenType::Minus => {
+                let (left, right) = self.check_number_operands(operator, &left, &right)?;
+                Ok(Object::Number(left - right))
+            }
+            TokenType::Slash => {
+                let (left, right) = self.check_number_operands(operator, &left, &right)?;
+                Ok(Object::Number(left / right))
+            }
+            TokenType::Star => {
+                let (left, right) = self.check_number_operands(operator, &left, &right)?;
+                Ok(Object::Number(left * right))
+            }
+            // The result takes the sign of the left operand, as in C.
+            TokenType::Percent => {
+                let (left, right) = self.check_number_operands(operator, &left, &right)?;
+                Ok(Object::Number(left % right))
+            }
+            TokenType::Plus => {
+                if let Ok((left, right)) = self.check_number_operands(operator, &left, &right) {
+                    return Ok(Object::Number(left + right));
+                }
+
+                if let (Object::String(left), Object::String(right)) = (left, right) {
+                    self.allocate(left.len() + right.len())?;
+                    return Ok(Object::String(format!("{}{}", left, right).into()));
+                }
+
+                Err(ErrorKind::AddOperands {
+                    operator: operator.clone(),
+                }
+                .into())
+            }
+            TokenType::Greater => {
+                let (left, right) = self.check_number_operands(operator, &left, &right)?;
+                Ok(Object::Bool(left > right))
+            }
+            TokenType::GreaterEqual => {
+                let (left, right) = self.check_number_operands(operator, &left, &right)?;
+                Ok(Object::Bool(left >= right))
+            }
+            TokenType::Less => {
+                let (left, right) = self.check_number_operands(operator, &left, &right)?;
+                Ok(Object::Bool(left < right))
+            }
+            TokenType::LessEqual => {
+                let (left, right) = self.check_number_operands(operator, &left, &right)?;
+                Ok(Object::Bool(left <= right))
+            }
+            TokenType::BangEqual => Ok(Object::Bool(!self.is_equal(&left, &right))),
+            TokenType::EqualEqual => Ok(Object::Bool(self.is_equal(&left, &right))),
+            _ => unreachable!(),
+        }
+    }
+
+    pub(crate) fn call(
+        &self,
+        callee: Object,
+        arguments: &[Object],
+    ) -> Result<Object, RuntimeError> {
+        let (native, receiver) = match &callee {
+            Object::Native(native) => (&**native, None),
+            Object::Method(method) => (method.native, Some(&method.receiver)),
+            _ => return Err(ErrorKind::NotCallable.into()),
+        };
+
+        if arguments.len() != native.arity {
+            return Err(ErrorKind::Arity {
+                expected: native.arity,
+                got: arguments.len(),
+            }
+            .into());
+        }
+
+        match receiver {
+            Some(receiver) => {
+                let mut arguments = arguments.to_vec();
+                arguments.insert(0, receiver.clone());
+                (native.function)(self, &arguments)
+            }
+            None => (native.function)(self, arguments),
+        }
+    }
+
+    fn get(&self, object: Object, name: &Token) -> Result<Object, RuntimeError> {
+        if let Object::Module(module) = &object {
+            return module
+                .globals
+                .borrow()
+                .get_local(&name.get_lexeme())
+                .ok_or_else(|| ErrorKind::UndefinedProperty { name: name.clone() }.into());
+        }
+
+        match natives::method(&object, &name.get_lexeme()) {
+            Some(native) => Ok(Object::Method(Rc::new(Method {
+                receiver: object,
+                native,
+            }))),
+            None => Err(ErrorKind::UndefinedProperty { name: name.clone() }.into()),
+        }
+    }
+
+    fn index(&self, object: &Object, index: &Object) -> Result<Object, RuntimeError> {
+        match object {
+            Object::List(list) => {
+                let list = list.borrow();
+                Ok(list[natives::index(index, list.len())?].clone())
+            }
+            Object::Map(map) => {
+                let key = natives::key(index)?;
+                match map.borrow().get(&key) {
+                    Some(value) => Ok(value.clone()),
+                    None => Err(ErrorKind::MissingKey {
+                        key: key.to_string(),
                     }
-                    TokenType::BangEqual => Ok(Literal::Bool(!self.is_equal(&left, &right))),
-                    TokenType::EqualEqual => Ok(Literal::Bool(self.is_equal(&left, &right))),
-                    _ => unreachable!(),
+                    .into()),
+                }
+            }
+            _ => Err(ErrorKind::NotIndexable.into()),
+        }
+    }
+
+    /// What a `for-in` loop over `iterable` goes through: the elements of a
+    /// list, the keys of a map or the characters of a string. They're taken
+    /// up front, so changing a collection inside the loop doesn't change
+    /// what the loop sees.
+    ///
+    /// There's no protocol for iterating over other values, such as an
+    /// `iter()` method returning something with `next()` and `done()`, as
+    /// programs can't define functions or classes to implement one with.
+    fn elements(&self, iterable: &Object) -> Result<Vec<Object>, RuntimeError> {
+        match iterable {
+            Object::List(list) => Ok(list.borrow().clone()),
+            Object::Map(map) => Ok(map.borrow().keys().map(Key::to_object).collect()),
+            Object::String(string) => {
+                self.allocate(string.len())?;
+                Ok(string
+                    .chars()
+                    .map(|char| Object::String(char.to_string().into()))
+                    .collect())
+            }
+            _ => Err(ErrorKind::NotIterable.into()),
+        }
+    }
+
+    fn set_index(
+        &self,
+        object: &Object,
+        index: &Object,
+        value: Object,
+    ) -> Result<(), RuntimeError> {
+        match object {
+            Object::List(list) => {
+                let mut list = list.borrow_mut();
+                let index = natives::index(index, list.len())?;
+                list[index] = value;
+                Ok(())
+            }
+            Object::Map(map) => {
+                let key = natives::key(index)?;
+                let mut map = map.borrow_mut();
+                if !map.contains_key(&key) {
+                    self.allocate(natives::ENTRY_SIZE)?;
                 }
+                map.insert(key, value);
+                Ok(())
             }
+            _ => Err(ErrorKind::NotIndexable.into()),
         }
     }
 
-    fn check_number_operand(&self, operator: &Token, a: &Literal) -> Result<f32, RuntimeError> {
-        if let Literal::Number(a) = a {
+    fn check_number_operand(&self, operator: &Token, a: &Object) -> Result<f32, RuntimeError> {
+        if let Object::Number(a) = a {
             return Ok(*a);
         };
 
-        Err(RuntimeError::InvalidOperands {
+        Err(ErrorKind::NumberOperand {
             operator: operator.clone(),
-        })
+        }
+        .into())
     }
 
     fn check_number_operands(
         &self,
         operator: &Token,
-        a: &Literal,
-        b: &Literal,
+        a: &Object,
+        b: &Object,
     ) -> Result<(f32, f32), RuntimeError> {
-        if let Literal::Number(a) = a {
-            if let Literal::Number(b) = b {
+        if let Object::Number(a) = a {
+            if let Object::Number(b) = b {
                 return Ok((*a, *b));
             };
         };
 
-        Err(RuntimeError::InvalidOperands {
+        Err(ErrorKind::NumberOperands {
             operator: operator.clone(),
-        })
+        }
+        .into())
     }
 
-    fn is_truthy(&self, literal: Literal) -> bool {
-        match literal {
-            Literal::Nil => false,
-            Literal::Bool(bool) => bool.to_owned(),
+    pub(crate) fn is_truthy(&self, value: Object) -> bool {
+        match value {
+            Object::Nil => false,
+            Object::Bool(bool) => bool.to_owned(),
             _ => true,
         }
     }
 
-    fn is_equal(&self, left: &Literal, right: &Literal) -> bool {
-        match (left, right) {
-            (Literal::Number(left), Literal::Number(right)) => left == right,
-            (Literal::String(left), Literal::String(right)) => left == right,
-            (Literal::Bool(left), Literal::Bool(right)) => left == right,
-            (Literal::Nil, Literal::Nil) => true,
-            _ => false,
+    fn is_equal(&self, left: &Object, right: &Object) -> bool {
+        left == right
+    }
+}
+
+/// A runtime error, along with the Lox call stack at the point it happened.
+#[derive(Error, Debug)]
+#[error("{kind}")]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    /// Innermost frame first.
+    pub stack: Vec<Frame>,
+}
+
+impl RuntimeError {
+    /// The message followed by one line per frame of the stack.
+    pub fn trace(&self) -> String {
+        let mut trace = self.kind.to_string();
+        for frame in &self.stack {
+            trace.push_str(&format!("\n{}", frame));
+        }
+        trace
+    }
+}
+
+impl From<ErrorKind> for RuntimeError {
+    fn from(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            stack: Vec::new(),
+        }
+    }
+}
+
+/// A function that was running when an error happened, and the line it had
+/// got to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub function: String,
+    pub file: Option<PathBuf>,
+    pub line: usize,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[line {}] in {}", self.line, self.function)?;
+        if let Some(file) = &self.file {
+            write!(f, " ({})", file.display())?;
         }
+        Ok(())
     }
 }
 
 #[derive(Error, Debug)]
-pub enum RuntimeError {
-    #[error("expected a different type")]
-    ExpectedType,
-    #[error("invalid operands for operator {operator:?}")]
-    InvalidOperands { operator: Token },
+pub enum ErrorKind {
+    #[error("Operand must be a number.")]
+    NumberOperand { operator: Token },
+    #[error("Operands must be numbers.")]
+    NumberOperands { operator: Token },
+    #[error("Operands must be two numbers or two strings.")]
+    AddOperands { operator: Token },
+    #[error("Undefined variable '{}'.", .name.get_lexeme())]
+    UndefinedVariable { name: Token },
+    #[error("Ran out of fuel.")]
+    OutOfFuel,
+    #[error("Ran past the deadline.")]
+    DeadlineExceeded,
+    #[error("Stack overflow.")]
+    StackOverflow,
+    #[error("Out of memory.")]
+    OutOfMemory,
+    #[error("Can only call functions and classes.")]
+    NotCallable,
+    #[error("Expected {expected} arguments but got {got}.")]
+    Arity { expected: usize, got: usize },
+    #[error("Argument must be {expected}.")]
+    ArgumentType { expected: &'static str },
+    #[error("Not allowed to {action}.")]
+    PermissionDenied { action: String },
+    #[error("Undefined property '{}'.", .name.get_lexeme())]
+    UndefinedProperty { name: Token },
+    #[error("Only lists and maps can be indexed.")]
+    NotIndexable,
+    #[error("Index must be an integer.")]
+    IndexType,
+    #[error("Index {index} is out of bounds for length {len}.")]
+    IndexOutOfBounds { index: f32, len: usize },
+    #[error("Can only iterate over lists, maps and strings.")]
+    NotIterable,
+    #[error("Map keys must be strings, numbers, booleans or nil.")]
+    KeyType,
+    #[error("Key {key} not found.")]
+    MissingKey { key: String },
+    #[error("Can't pop from an empty list.")]
+    EmptyList,
+    #[error("Can only sort lists of numbers or lists of strings.")]
+    Unsortable,
+    #[error("Couldn't write output: {0}.")]
+    Output(io::Error),
+    #[error("Couldn't {action} '{path}': {error}.")]
+    Io {
+        action: &'static str,
+        path: String,
+        error: io::Error,
+    },
+    #[error("Import cycle: '{path}' is already being imported.")]
+    ImportCycle { path: String },
+    #[error("Couldn't import '{path}': {message}")]
+    ImportSyntax { path: String, message: String },
+    /// Not really an error: the program asked to stop with this status.
+    #[error("Exited with status {0}.")]
+    Exit(i32),
+    #[error("Exit code must be between 0 and 255.")]
+    ExitCode,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{buffer::Buffer, lexer::Lexer, parser::Parser, token::Literal};
+
+    fn interpret(interpreter: Interpreter, source: &str) -> RuntimeError {
+        let mut interpreter = interpreter;
+        let statements = Parser::new(Lexer::new(source).scan_tokens())
+            .parse()
+            .unwrap();
+        interpreter.interpret(&statements).unwrap_err()
+    }
+
+    #[test]
+    fn stack_traces() {
+        let error = interpret(
+            Interpreter::new(),
+            "var a = 1;\n{\n    a = a\n      + b;\n}",
+        );
+        assert!(matches!(error.kind, ErrorKind::UndefinedVariable { .. }));
+        assert_eq!(
+            error.stack,
+            vec![Frame {
+                function: "script".to_owned(),
+                file: None,
+                line: 4,
+            }]
+        );
+
+        let error = interpret(Interpreter::new().file("main.lox"), "print 1;\n\n-nil;");
+        assert_eq!(
+            error.trace(),
+            format!("{}\n[line 3] in script (main.lox)", error.kind)
+        );
+    }
+
+    #[test]
+    fn calls() {
+        let error = interpret(Interpreter::new(), "print clock;\nclock(\n1);");
+        assert!(matches!(
+            error.kind,
+            ErrorKind::Arity {
+                expected: 0,
+                got: 1
+            }
+        ));
+        assert_eq!(error.stack[0].line, 3);
+
+        assert!(matches!(
+            interpret(Interpreter::new(), "nil();").kind,
+            ErrorKind::NotCallable
+        ));
+    }
+
+    #[test]
+    fn lists() {
+        let error = interpret(Interpreter::new(), "var xs = [1];\nxs[\n1];");
+        assert!(matches!(
+            error.kind,
+            ErrorKind::IndexOutOfBounds { len: 1, .. }
+        ));
+        assert_eq!(error.stack[0].line, 3);
+
+        let size = mem::size_of::<Object>();
+        let error = interpret(
+            Interpreter::new().max_allocated(2 * size),
+            "var xs = [1, 2];\nxs.pop();\nxs.push(3);",
+        );
+        assert!(matches!(error.kind, ErrorKind::OutOfMemory));
+        assert_eq!(error.stack[0].line, 3);
+    }
+
+    #[test]
+    fn maps() {
+        let error = interpret(Interpreter::new(), "var m = {\n[]: 1};");
+        assert!(matches!(error.kind, ErrorKind::KeyType));
+        assert_eq!(error.stack[0].line, 2);
+
+        let error = interpret(Interpreter::new(), "var m = {1: 2};\nm[1.5];");
+        assert_eq!(error.kind.to_string(), "Key 1.5 not found.");
+
+        let size = natives::ENTRY_SIZE;
+        let error = interpret(
+            Interpreter::new().max_allocated(2 * size),
+            "var m = {\"a\": 1, \"b\": 2};\nm[\"a\"] = 3;\nm[\"c\"] = 4;",
+        );
+        assert!(matches!(error.kind, ErrorKind::OutOfMemory));
+        assert_eq!(error.stack[0].line, 3);
+    }
+
+    #[test]
+    fn for_in() {
+        let error = interpret(Interpreter::new(), "for (var x\nin nil)\nprint x;");
+        assert!(matches!(error.kind, ErrorKind::NotIterable));
+        assert_eq!(error.stack[0].line, 1);
+
+        let error = interpret(
+            Interpreter::new().max_allocated(1000),
+            "for (var i in range(0, 16777216)) print i;",
+        );
+        assert!(matches!(error.kind, ErrorKind::OutOfMemory));
+
+        let error = interpret(
+            Interpreter::new().fuel(10),
+            "for (var i in range(0, 10)) i;",
+        );
+        assert!(matches!(error.kind, ErrorKind::OutOfFuel));
+    }
+
+    #[test]
+    fn strings() {
+        let error = interpret(
+            Interpreter::new().max_allocated(5),
+            "var s = \"abcdef\";\ns.upper();",
+        );
+        assert!(matches!(error.kind, ErrorKind::OutOfMemory));
+        assert_eq!(error.stack[0].line, 2);
+
+        // Copies of a string share it, so they don't cost anything more.
+        let mut interpreter = Interpreter::new();
+        let source = "var s = \"abc\";\nvar xs = [s, str(s)];";
+        let statements = Parser::new(Lexer::new(source).scan_tokens())
+            .parse()
+            .unwrap();
+        interpreter.interpret(&statements).unwrap();
+        let globals = interpreter.globals();
+        let value = |name: &str| {
+            let (_, value) = globals.iter().find(|(global, _)| global == name).unwrap();
+            value.clone()
+        };
+        match (value("s"), value("xs")) {
+            (Object::String(s), Object::List(list)) => assert!(list.borrow().iter().all(
+                |element| matches!(element, Object::String(e) if std::sync::Arc::ptr_eq(e, &s))
+            )),
+            values => panic!("unexpected {:?}", values),
+        }
+
+        let error = interpret(Interpreter::new(), "\"a\".len(1);");
+        assert!(matches!(
+            error.kind,
+            ErrorKind::Arity {
+                expected: 0,
+                got: 1
+            }
+        ));
+    }
+
+    #[test]
+    fn seeds() {
+        let output = Buffer::new();
+        let statements = Parser::new(Lexer::new("print random();").scan_tokens())
+            .parse()
+            .unwrap();
+        for _ in 0..2 {
+            let mut interpreter = Interpreter::new().seed(7).output(output.clone());
+            interpreter.interpret(&statements).unwrap();
+        }
+
+        let contents = output.contents();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[0], lines[1]);
+    }
+
+    #[test]
+    fn output() {
+        let output = Buffer::new();
+        let errors = Buffer::new();
+        let mut interpreter = Interpreter::new()
+            .output(output.clone())
+            .errors(errors.clone());
+
+        let statements = Parser::new(Lexer::new("print 1 + 2;\nprint \"a\";").scan_tokens())
+            .parse()
+            .unwrap();
+        interpreter.interpret(&statements).unwrap();
+        assert_eq!(output.contents(), "3\na\n");
+
+        let error = interpret(interpreter, "print -nil;");
+        assert_eq!(output.contents(), "3\na\n");
+        assert!(errors.contents().is_empty());
+
+        Interpreter::new()
+            .errors(errors.clone())
+            .report(&error.trace());
+        assert_eq!(
+            errors.contents(),
+            "Operand must be a number.\n[line 1] in script\n"
+        );
+    }
+
+    #[test]
+    fn capabilities() {
+        let locked_down = || Interpreter::new().capabilities(Capabilities::none());
+
+        let error = interpret(locked_down(), "var a = 1;\nprint a;");
+        assert_eq!(error.kind.to_string(), "Not allowed to write to stdout.");
+        assert_eq!(error.stack[0].line, 2);
+
+        let error = interpret(locked_down(), "clock();");
+        assert_eq!(error.kind.to_string(), "Not allowed to read the clock.");
+
+        let error = interpret(locked_down(), "seed(1);\nrandom();");
+        assert_eq!(
+            error.kind.to_string(),
+            "Not allowed to generate random numbers."
+        );
+
+        let mut interpreter = locked_down();
+        let statements = Parser::new(Lexer::new("var a = 1 + 2;").scan_tokens())
+            .parse()
+            .unwrap();
+        assert!(interpreter.interpret(&statements).is_ok());
+    }
+
+    #[test]
+    fn io() {
+        let root = std::env::temp_dir().join(format!("glad-io-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        let source = format!(
+            "var root = \"{}\";\n\
+             writeFile(root + \"/a.txt\", \"one\\ntwo\");\n\
+             print readFile(root + \"/a.txt\").split(\"\\n\");\n\
+             print listDir(root);\n\
+             print readLine();\n\
+             print readLine();\n\
+             print readLine();\n\
+             print args();\n\
+             readFile(root + \"/missing.txt\");",
+            root.display()
+        );
+
+        let output = Buffer::new();
+        let interpreter = Interpreter::new()
+            .input(&b"first\r\nsecond"[..])
+            .args(vec!["x".to_owned()])
+            .output(output.clone());
+        let error = interpret(interpreter, &source);
+        assert_eq!(
+            output.contents(),
+            "[\"one\", \"two\"]\n[\"a.txt\", \"sub\"]\nfirst\nsecond\nnil\n[\"x\"]\n"
+        );
+        assert!(matches!(error.kind, ErrorKind::Io { action: "read", .. }));
+        assert_eq!(error.stack[0].line, 9);
+
+        let sandboxed = Interpreter::new().capabilities(Capabilities {
+            read: Some(vec![root.join("sub")]),
+            ..Capabilities::none()
+        });
+        let error = interpret(sandboxed, &source);
+        assert!(matches!(error.kind, ErrorKind::PermissionDenied { .. }));
+        assert_eq!(error.stack[0].line, 2);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn imports() {
+        let root = std::env::temp_dir().join(format!("glad-imports-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.lox"), "var a = 1;\nprint main;").unwrap();
+        std::fs::write(root.join("b.lox"), "var b = 1;\nb +;").unwrap();
+
+        // A module can't see the globals of the file importing it.
+        let error = interpret(
+            Interpreter::new().file(root.join("main.lox")),
+            "var main = 1;\nimport \"a.lox\" as a;",
+        );
+        assert_eq!(
+            error.trace(),
+            format!(
+                "Undefined variable 'main'.\n[line 2] in script ({})\n[line 2] in script ({})",
+                root.join("a.lox").display(),
+                root.join("main.lox").display()
+            )
+        );
+
+        let error = interpret(
+            Interpreter::new().file(root.join("main.lox")),
+            "\nimport \"b.lox\" as b;",
+        );
+        assert_eq!(
+            error.kind.to_string(),
+            "Couldn't import 'b.lox': [line 2] Error at ';': Expect expression."
+        );
+        assert_eq!(error.stack[0].line, 2);
+
+        let error = interpret(
+            Interpreter::new().file(root.join("main.lox")),
+            "import \"c.lox\" as c;",
+        );
+        assert!(matches!(
+            error.kind,
+            ErrorKind::Io {
+                action: "import",
+                ..
+            }
+        ));
+
+        let sandboxed = Interpreter::new()
+            .file(root.join("main.lox"))
+            .capabilities(Capabilities::none());
+        let error = interpret(sandboxed, "import \"a.lox\" as a;");
+        assert_eq!(error.kind.to_string(), "Not allowed to read 'a.lox'.");
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn budgets() {
+        // Each statement and expression takes a step.
+        let source = "var a = 1 + 2;\nprint a;";
+        assert!(matches!(
+            interpret(Interpreter::new().fuel(4), source).kind,
+            ErrorKind::OutOfFuel
+        ));
+        assert!(matches!(
+            interpret(Interpreter::new().fuel(6).deadline(Instant::now()), source).kind,
+            ErrorKind::DeadlineExceeded
+        ));
+
+        let mut interpreter = Interpreter::new().fuel(0);
+        interpreter.set_budget(Some(6), None);
+        let statements = Parser::new(Lexer::new(source).scan_tokens())
+            .parse()
+            .unwrap();
+        assert!(interpreter.interpret(&statements).is_ok());
+
+        assert!(matches!(
+            interpret(Interpreter::new().fuel(1000), "while (true) {}").kind,
+            ErrorKind::OutOfFuel
+        ));
+    }
+
+    #[test]
+    fn stack_overflow() {
+        let source = "print -(-(-(-1)));";
+        assert!(matches!(
+            interpret(Interpreter::new().max_depth(4), source).kind,
+            ErrorKind::StackOverflow
+        ));
+        assert!(matches!(
+            interpret(Interpreter::new().max_depth(4), "{{{{{}}}}}").kind,
+            ErrorKind::StackOverflow
+        ));
+
+        // Far deeper than the default limit, which has to be hit before
+        // anything runs out of native stack.
+        let expr = (0..100_000).fold(
+            Expr::Literal {
+                literal: Literal::Number(1.0),
+            },
+            |right, _| Expr::Unary {
+                operator: Token::new(TokenType::Minus, "-".to_owned(), None, 1),
+                right: Box::new(right),
+            },
+        );
+        let error = Interpreter::new().evaluate(&expr).unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::StackOverflow));
+    }
+
+    #[test]
+    fn out_of_memory() {
+        let source = "var a = \"ab\";\na = a + a;\na = a + a;\na = a + a;";
+        let error = interpret(Interpreter::new().max_allocated(11), source);
+        assert!(matches!(error.kind, ErrorKind::OutOfMemory));
+        assert_eq!(error.stack[0].line, 3);
+    }
 }
@@ -6,25 +6,50 @@ use crate::{
 };
 
 #[derive(Debug)]
-pub struct Lexer<'a> {
-    source: &'a str,
+pub struct Lexer {
+    /// The source split into chars, which is what `start` and `current`
+    /// count.
+    source: Vec<char>,
     tokens: Vec<Token>,
     start: usize,
     current: usize,
     line: usize,
+    keep_comments: bool,
+    lossless: bool,
 }
 
-impl<'a> Lexer<'a> {
-    pub fn new(source: &'a str) -> Self {
+impl Lexer {
+    pub fn new(source: &str) -> Self {
         Self {
-            source,
+            source: source.chars().collect(),
             tokens: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
+            keep_comments: false,
+            lossless: false,
         }
     }
 
+    /// Emit `//` comments as `Comment` tokens instead of discarding them.
+    /// The parser doesn't understand these, so this is only for tooling that
+    /// needs to reproduce the source, like the formatter.
+    pub fn keep_comments(mut self) -> Self {
+        self.keep_comments = true;
+        self
+    }
+
+    /// Emit every character of the source as part of some token, so the
+    /// tokens can be concatenated back into the original text. On top of
+    /// comments this keeps runs of whitespace as `Whitespace` tokens.
+    pub fn lossless(mut self) -> Self {
+        self.keep_comments = true;
+        self.lossless = true;
+        self
+    }
+
+    /// Unexpected characters and unterminated strings come out as `Error`
+    /// tokens, for the parser to report.
     pub fn scan_tokens(&mut self) -> Vec<Token> {
         while !self.is_at_end() {
             self.start = self.current;
@@ -38,7 +63,7 @@ impl<'a> Lexer<'a> {
     }
 
     fn is_at_end(&self) -> bool {
-        self.current >= self.source.len().try_into().unwrap()
+        self.current >= self.source.len()
     }
 
     fn scan_token(&mut self) {
@@ -48,13 +73,17 @@ impl<'a> Lexer<'a> {
             ')' => Some(TokenType::RightParen),
             '{' => Some(TokenType::LeftBrace),
             '}' => Some(TokenType::RightBrace),
+            '[' => Some(TokenType::LeftBracket),
+            ']' => Some(TokenType::RightBracket),
             ',' => Some(TokenType::Comma),
+            ':' => Some(TokenType::Colon),
             '(' => Some(TokenType::LeftParen),
             '.' => Some(TokenType::Dot),
             '-' => Some(TokenType::Minus),
             '+' => Some(TokenType::Plus),
             ';' => Some(TokenType::Semicolon),
             '*' => Some(TokenType::Star),
+            '%' => Some(TokenType::Percent),
             '!' => {
                 if self.matches('=') {
                     Some(TokenType::BangEqual)
@@ -88,15 +117,31 @@ impl<'a> Lexer<'a> {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
-                    None
+
+                    if self.keep_comments {
+                        Some(TokenType::Comment)
+                    } else {
+                        None
+                    }
                 } else {
                     Some(TokenType::Slash)
                 }
             }
-            ' ' | '\r' | '\t' => None,
-            '\n' => {
-                self.line += 1;
-                None
+            ' ' | '\r' | '\t' | '\n' => {
+                if c == '\n' {
+                    self.line += 1;
+                }
+
+                if self.lossless {
+                    while matches!(self.peek(), ' ' | '\r' | '\t' | '\n') {
+                        if self.advance() == '\n' {
+                            self.line += 1;
+                        }
+                    }
+                    Some(TokenType::Whitespace)
+                } else {
+                    None
+                }
             }
             '"' => {
                 self.string();
@@ -107,7 +152,7 @@ impl<'a> Lexer<'a> {
                 None
             }
             c if c.is_alphabetic() => Some(self.identifer()),
-            _ => panic!("Unexpected character"),
+            _ => Some(TokenType::Error),
         };
 
         if let Some(matched_token) = matched {
@@ -116,7 +161,7 @@ impl<'a> Lexer<'a> {
     }
 
     fn advance(&mut self) -> char {
-        let cur = self.source.chars().nth(self.current as usize).unwrap();
+        let cur = self.source[self.current];
         self.current += 1;
         cur
     }
@@ -126,21 +171,20 @@ impl<'a> Lexer<'a> {
     }
 
     fn add_token_literal(&mut self, token_type: TokenType, literal: Option<Literal>) {
-        let text: String = self
-            .source
-            .chars()
-            .skip(self.start as usize)
-            .take((self.current - self.start).try_into().unwrap())
-            .collect();
+        let text = self.text(self.start, self.current);
         self.tokens
             .push(Token::new(token_type, text, literal, self.line))
     }
 
+    fn text(&self, start: usize, end: usize) -> String {
+        self.source[start..end].iter().collect()
+    }
+
     fn matches(&mut self, expected: char) -> bool {
         if self.is_at_end() {
             return false;
         };
-        if self.source.chars().nth(self.current as usize).unwrap() != expected {
+        if self.source[self.current] != expected {
             return false;
         };
 
@@ -152,7 +196,7 @@ impl<'a> Lexer<'a> {
         if self.is_at_end() {
             return '\0';
         };
-        self.source.chars().nth(self.current as usize).unwrap()
+        self.source[self.current]
     }
 
     fn peek_next(&self) -> char {
@@ -160,7 +204,7 @@ impl<'a> Lexer<'a> {
             return '\0';
         };
 
-        self.source.chars().nth(self.current + 1 as usize).unwrap()
+        self.source[self.current + 1]
     }
 
     fn string(&mut self) {
@@ -172,44 +216,35 @@ impl<'a> Lexer<'a> {
         }
 
         if self.is_at_end() {
-            panic!("unterminated string");
+            self.add_token(TokenType::Error);
+            return;
         }
 
         self.advance();
 
-        let value: String = self
-            .source
-            .chars()
-            .skip(self.start + 1)
-            .take(self.current - self.start - 2)
-            .collect();
-        self.add_token_literal(TokenType::String, Some(Literal::String(value)));
+        let value = self.text(self.start + 1, self.current - 1);
+        self.add_token_literal(TokenType::String, Some(Literal::String(value.into())));
     }
 
     fn number(&mut self) {
-        while self.peek().is_digit(10) {
+        while self.peek().is_ascii_digit() {
             self.advance();
         }
 
         // Look for a fractional part.
-        if self.peek() == '.' && self.peek_next().is_digit(10) {
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             self.advance();
 
-            while self.peek().is_digit(10) {
+            while self.peek().is_ascii_digit() {
                 self.advance();
             }
         }
 
         self.add_token_literal(
             TokenType::Number,
+            // Digits with an optional fractional part always parse.
             Some(Literal::Number(
-                self.source
-                    .chars()
-                    .skip(self.start)
-                    .take(self.current - self.start)
-                    .collect::<String>()
-                    .parse()
-                    .unwrap(),
+                self.text(self.start, self.current).parse().unwrap(),
             )),
         )
     }
@@ -218,23 +253,17 @@ impl<'a> Lexer<'a> {
         while self.peek().is_alphanumeric() {
             self.advance();
         }
-        let text: String = self
-            .source
-            .chars()
-            .skip(self.start)
-            .take(self.current - self.start)
-            .collect::<String>()
-            .parse()
-            .unwrap();
-
-        match text.as_str() {
+        match self.text(self.start, self.current).as_str() {
             "and" => TokenType::And,
+            "as" => TokenType::As,
             "class" => TokenType::Class,
             "else" => TokenType::Else,
             "false" => TokenType::False,
             "for" => TokenType::For,
             "fun" => TokenType::Fun,
             "if" => TokenType::If,
+            "import" => TokenType::Import,
+            "in" => TokenType::In,
             "nil" => TokenType::Nil,
             "or" => TokenType::Or,
             "print" => TokenType::Print,
@@ -260,4 +289,64 @@ mod tests {
 
         dbg!(lexer);
     }
+
+    #[test]
+    fn errors() {
+        let tokens = Lexer::new("1 @ \"open\n").scan_tokens();
+        let types: Vec<_> = tokens.iter().map(|t| t.token_type.clone()).collect();
+
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Number,
+                TokenType::Error,
+                TokenType::Error,
+                TokenType::Eof
+            ]
+        );
+        assert_eq!(tokens[2].get_lexeme(), "\"open\n");
+    }
+
+    #[test]
+    fn comments() {
+        let source = "1 // one\n+ 2";
+
+        let tokens = Lexer::new(source).scan_tokens();
+        assert!(tokens.iter().all(|t| t.token_type != TokenType::Comment));
+
+        let tokens = Lexer::new(source).keep_comments().scan_tokens();
+        assert_eq!(tokens[1].token_type, TokenType::Comment);
+        assert_eq!(tokens[1].get_lexeme(), "// one");
+        assert_eq!(tokens[2].get_line(), 2);
+    }
+
+    #[test]
+    fn lossless() {
+        let source = "(1 +\t\"a\") @ // é\n\"open";
+        let tokens = Lexer::new(source).lossless().scan_tokens();
+
+        let text: String = tokens.iter().map(|t| t.get_lexeme()).collect();
+        assert_eq!(text, source);
+
+        let types: Vec<_> = tokens.iter().map(|t| t.token_type.clone()).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::LeftParen,
+                TokenType::Number,
+                TokenType::Whitespace,
+                TokenType::Plus,
+                TokenType::Whitespace,
+                TokenType::String,
+                TokenType::RightParen,
+                TokenType::Whitespace,
+                TokenType::Error,
+                TokenType::Whitespace,
+                TokenType::Comment,
+                TokenType::Whitespace,
+                TokenType::Error,
+                TokenType::Eof,
+            ]
+        );
+    }
 }
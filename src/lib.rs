@@ -1,42 +1,517 @@
+mod buffer;
+mod capabilities;
+pub mod cst;
+mod environment;
 mod expr;
+mod formatter;
+#[cfg(test)]
+mod generator;
 mod interpreter;
 mod lexer;
+pub mod lsp;
+mod natives;
+mod object;
+mod optimizer;
 mod parser;
+mod printer;
+pub mod repl;
+mod stmt;
 mod token;
-mod token_type;
+pub mod token_type;
 
-use interpreter::Interpreter;
-use lexer::Lexer;
-use parser::Parser;
+pub use buffer::Buffer;
+pub use capabilities::Capabilities;
+pub use interpreter::{ErrorKind, Frame, Interpreter, RuntimeError};
+pub use lexer::Lexer;
+pub use object::{Native, Object};
+pub use parser::Parser;
 
-use std::path::PathBuf;
+use formatter::Formatter;
+use optimizer::Optimizer;
+use parser::ParseError;
+use printer::Printer;
+use rustyline::error::ReadlineError;
 
-use anyhow::Result;
-use clap::Parser as ClapParser;
+use stmt::Stmt;
+use token::Token;
 
-#[derive(ClapParser, Debug)]
-#[clap(version)]
+use std::{
+    fs::{read_to_string, write},
+    io::{self, Read},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Error, Result};
+use clap::{Parser as ClapParser, Subcommand, ValueEnum};
+
+#[derive(ClapParser, Debug, Default, Clone)]
+#[clap(version, args_conflicts_with_subcommands = true)]
 pub struct Args {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// Without a subcommand, run this script, or start a REPL if there isn't
+    /// one
+    #[clap(flatten)]
+    pub input: Input,
+
+    /// Fold constant expressions before evaluating them
+    #[clap(short = 'O', long, global = true)]
+    pub optimize: bool,
+
+    /// Print the tokens before running the program
+    #[clap(long, global = true)]
+    pub dump_tokens: bool,
+
+    /// Print the syntax tree before running the program
+    #[clap(long, global = true)]
+    pub dump_ast: bool,
+
+    /// Print syntax trees as Lox source rather than as S-expressions
+    #[clap(long, global = true)]
+    pub print_source: bool,
+
+    /// Stop after executing this many statements and expressions
+    #[clap(long, global = true, value_name = "STEPS")]
+    pub fuel: Option<u64>,
+
+    /// Stop if the program is still running after this many milliseconds
+    #[clap(long, global = true, value_name = "MS")]
+    pub timeout: Option<u64>,
+
+    /// Raise a stack overflow error when expressions and blocks nest deeper
+    /// than this
+    #[clap(long, global = true, value_name = "DEPTH")]
+    pub max_depth: Option<usize>,
+
+    /// Raise an out of memory error once the program has created this many
+    /// bytes of strings, lists and maps in total, including any it has since
+    /// dropped
+    #[clap(long, global = true, value_name = "BYTES")]
+    pub max_allocated: Option<usize>,
+
+    /// Deny the program access to anything outside the interpreter, even
+    /// printing, except what is allowed with the other sandbox flags
+    #[clap(long, global = true)]
+    pub sandbox: bool,
+
+    /// Let a sandboxed program do this
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        value_name = "CAPABILITY",
+        requires = "sandbox"
+    )]
+    pub allow: Vec<Capability>,
+
+    /// Let a sandboxed program read files under this directory
+    #[clap(long, global = true, value_name = "DIR", requires = "sandbox")]
+    pub allow_read: Vec<PathBuf>,
+
+    /// Let a sandboxed program write files under this directory
+    #[clap(long, global = true, value_name = "DIR", requires = "sandbox")]
+    pub allow_write: Vec<PathBuf>,
+}
+
+/// Things that can be allowed one by one in the sandbox.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Capability {
+    Stdout,
+    Stdin,
+    Clock,
+    Env,
+    Random,
+}
+
+impl Args {
+    /// When a program started now would have to stop by.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.timeout
+            .map(|timeout| Instant::now() + Duration::from_millis(timeout))
+    }
+
+    /// An interpreter with the limits asked for, with its budget starting
+    /// now.
+    pub(crate) fn interpreter(&self) -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        if let Some(max_depth) = self.max_depth {
+            interpreter = interpreter.max_depth(max_depth);
+        }
+        if let Some(max_allocated) = self.max_allocated {
+            interpreter = interpreter.max_allocated(max_allocated);
+        }
+        interpreter = interpreter.capabilities(self.capabilities());
+        interpreter.set_budget(self.fuel, self.deadline());
+
+        interpreter
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        if !self.sandbox {
+            return Capabilities::all();
+        }
+
+        let mut capabilities = Capabilities {
+            read: Some(self.allow_read.clone()),
+            write: Some(self.allow_write.clone()),
+            ..Capabilities::none()
+        };
+        for capability in &self.allow {
+            match capability {
+                Capability::Stdout => capabilities.stdout = true,
+                Capability::Stdin => capabilities.stdin = true,
+                Capability::Clock => capabilities.clock = true,
+                Capability::Env => capabilities.env = true,
+                Capability::Random => capabilities.random = true,
+            }
+        }
+
+        capabilities
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Run a script
+    Run {
+        #[clap(flatten)]
+        input: Input,
+    },
+
+    /// Start an interactive session
+    Repl,
+
+    /// Check a script for syntax errors without running it
+    Check {
+        #[clap(flatten)]
+        input: Input,
+    },
+
+    /// Print the tokens in a script
+    Tokens {
+        #[clap(flatten)]
+        input: Input,
+    },
+
+    /// Print the syntax tree of a script
+    Ast {
+        #[clap(flatten)]
+        input: Input,
+    },
+
+    /// Format Lox source files in place
+    Fmt {
+        #[clap(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Don't write anything, just fail if any file isn't formatted
+        #[clap(long)]
+        check: bool,
+    },
+
+    /// Run a language server over stdio
+    Lsp,
+}
+
+// Where a program's source comes from. (Not a doc comment, which clap would
+// take as the description of every command that includes it.)
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct Input {
+    /// Path to the script, or `-` to read it from stdin
+    #[clap(conflicts_with = "eval")]
     pub file: Option<PathBuf>,
+
+    /// Use this code instead of a script
+    #[clap(short, long, value_name = "CODE")]
+    pub eval: Option<String>,
+
+    /// Arguments for the script, which it can get with `args()`
+    #[clap(value_name = "ARGS")]
+    pub args: Vec<String>,
+}
+
+impl Input {
+    pub fn is_empty(&self) -> bool {
+        self.file.is_none() && self.eval.is_none()
+    }
+
+    pub fn read(&self) -> Result<String> {
+        match (&self.file, &self.eval) {
+            (_, Some(code)) => Ok(code.clone()),
+            (Some(file), None) if file.as_os_str() == "-" => {
+                let mut source = String::new();
+                io::stdin()
+                    .read_to_string(&mut source)
+                    .context("couldn't read stdin")?;
+                Ok(source)
+            }
+            (Some(file), None) => {
+                read_to_string(file).with_context(|| format!("couldn't read {}", file.display()))
+            }
+            (None, None) => bail!("expected a script, `-` for stdin, or -e <CODE>"),
+        }
+    }
+}
+
+/// Exit status for input that doesn't compile (`EX_DATAERR` in sysexits.h).
+pub const EXIT_COMPILE_ERROR: i32 = 65;
+/// Exit status for errors while the program runs (`EX_SOFTWARE`).
+pub const EXIT_RUNTIME_ERROR: i32 = 70;
+/// Exit status for failing to read or write a file (`EX_IOERR`).
+pub const EXIT_IO_ERROR: i32 = 74;
+
+/// The status the program asked to exit with, if that's what `error` is.
+pub fn exit_status(error: &Error) -> Option<i32> {
+    match error.downcast_ref::<RuntimeError>() {
+        Some(RuntimeError {
+            kind: ErrorKind::Exit(status),
+            ..
+        }) => Some(*status),
+        _ => None,
+    }
+}
+
+/// The exit status a process should end with after `error`.
+pub fn exit_code(error: &Error) -> i32 {
+    if let Some(status) = exit_status(error) {
+        status
+    } else if error.downcast_ref::<ParseError>().is_some() {
+        EXIT_COMPILE_ERROR
+    } else if error.downcast_ref::<RuntimeError>().is_some() {
+        EXIT_RUNTIME_ERROR
+    } else if error.downcast_ref::<io::Error>().is_some()
+        || error.downcast_ref::<ReadlineError>().is_some()
+    {
+        EXIT_IO_ERROR
+    } else {
+        1
+    }
 }
 
-pub fn run(source: &str) -> Result<()> {
-    let mut lexer = Lexer::new(&source);
+/// How `error` should be shown to the user. Lox errors are shown the way
+/// the reference implementation shows them.
+pub fn describe(error: &Error) -> String {
+    if error.downcast_ref::<ParseError>().is_some() {
+        format!("{:#}", error)
+    } else if let Some(error) = error.downcast_ref::<RuntimeError>() {
+        error.trace()
+    } else {
+        format!("Error: {:#}", error)
+    }
+}
+
+pub fn run(input: &Input, args: &Args) -> Result<()> {
+    let source = input.read()?;
+    let tokens = Lexer::new(&source).scan_tokens();
+
+    if args.dump_tokens {
+        print!("{}", dump_tokens(&tokens));
+    }
+
+    let mut statements = Parser::new(tokens).parse()?;
+    if args.optimize {
+        statements = Optimizer::new().optimize(&statements);
+    }
 
-    let tokens = lexer.scan_tokens();
-    // dbg!(&lexer);
+    if args.dump_ast {
+        print!("{}", dump_ast(&statements, args.print_source));
+    }
 
-    let mut parser = Parser::new(tokens);
+    let mut interpreter = args.interpreter().args(input.args.clone());
+    if let Some(file) = input.file.as_ref().filter(|file| file.as_os_str() != "-") {
+        interpreter = interpreter.file(file);
+    }
+    interpreter.interpret(&statements)?;
+
+    Ok(())
+}
+
+/// Parses a program without running it.
+pub fn check(source: &str) -> Result<()> {
+    Parser::new(Lexer::new(source).scan_tokens()).parse()?;
+    Ok(())
+}
 
-    match parser.parse() {
-        Ok(expression) => {
-            // dbg!(&expression);
-            println!("{}", expression);
-            let mut interpreter = Interpreter::new();
-            interpreter.interpret(&expression)?;
+pub fn tokens(source: &str) -> Result<()> {
+    print!("{}", dump_tokens(&Lexer::new(source).scan_tokens()));
+    Ok(())
+}
+
+pub fn ast(source: &str, args: &Args) -> Result<()> {
+    let mut statements = Parser::new(Lexer::new(source).scan_tokens()).parse()?;
+    if args.optimize {
+        statements = Optimizer::new().optimize(&statements);
+    }
+
+    print!("{}", dump_ast(&statements, args.print_source));
+    Ok(())
+}
+
+/// One token per line, with its line number, type, lexeme and any literal.
+fn dump_tokens(tokens: &[Token]) -> String {
+    let mut output = String::new();
+
+    for token in tokens {
+        output.push_str(&format!(
+            "{:>3} {:?} {}",
+            token.get_line(),
+            token.token_type,
+            token.get_lexeme()
+        ));
+        if let Some(literal) = &token.literal {
+            output.push_str(&format!(" {:?}", literal));
         }
-        Err(_) => println!(),
+        output.push('\n');
+    }
+
+    output
+}
+
+fn dump_ast(statements: &[Stmt], print_source: bool) -> String {
+    if print_source {
+        return Printer::new().print(statements);
+    }
+
+    statements
+        .iter()
+        .map(|statement| format!("{}\n", statement))
+        .collect()
+}
+
+pub fn fmt(files: &[PathBuf], check: bool) -> Result<()> {
+    let formatter = Formatter::new();
+    let mut unformatted = 0;
+
+    for file in files {
+        let source =
+            read_to_string(file).with_context(|| format!("couldn't read {}", file.display()))?;
+        let formatted = formatter
+            .format(&source)
+            .with_context(|| file.display().to_string())?;
+
+        if formatted != source {
+            if check {
+                eprintln!("{} is not formatted", file.display());
+                unformatted += 1;
+            } else {
+                write(file, formatted)
+                    .with_context(|| format!("couldn't write {}", file.display()))?;
+            }
+        }
+    }
+
+    if unformatted > 0 {
+        bail!("{} of {} files need formatting", unformatted, files.len());
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments() {
+        let args = Args::try_parse_from(["glad", "script.lox", "-O"]).unwrap();
+        assert!(args.command.is_none());
+        assert_eq!(args.input.file, Some(PathBuf::from("script.lox")));
+        assert!(args.optimize);
+
+        let args = Args::try_parse_from(["glad", "run", "-e", "print 1;", "--dump-ast"]).unwrap();
+        match args.command {
+            Some(Command::Run { input }) => assert_eq!(input.read().unwrap(), "print 1;"),
+            command => panic!("expected run, got {:?}", command),
+        }
+        assert!(args.dump_ast);
+
+        assert!(Args::try_parse_from(["glad", "script.lox", "-e", "1;"]).is_err());
+        assert!(Args::try_parse_from(["glad", "-e", "1;", "script.lox"]).is_err());
+        assert!(Args::default().input.read().is_err());
+
+        let args = Args::try_parse_from([
+            "glad",
+            "--sandbox",
+            "--allow",
+            "stdout",
+            "--allow-read",
+            ".",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.capabilities(),
+            Capabilities {
+                read: Some(vec![PathBuf::from(".")]),
+                stdout: true,
+                ..Capabilities::none()
+            }
+        );
+        assert!(Args::try_parse_from(["glad", "--allow", "clock"]).is_err());
+
+        // Anything after the script is passed to it, even subcommand names.
+        let args = Args::try_parse_from(["glad", "script.lox", "repl", "-O", "--", "-x"]).unwrap();
+        assert_eq!(args.input.args, vec!["repl", "-x"]);
+        assert!(args.optimize);
+    }
+
+    #[test]
+    fn exit_codes() {
+        let args = Args::default();
+        let code = |source: &str| {
+            let input = Input {
+                eval: Some(source.to_owned()),
+                ..Input::default()
+            };
+            exit_code(&run(&input, &args).unwrap_err())
+        };
+
+        assert_eq!(code("print 1"), EXIT_COMPILE_ERROR);
+        assert_eq!(code("print -nil;"), EXIT_RUNTIME_ERROR);
+        assert_eq!(code("exit(3);"), 3);
+        assert_eq!(code("exit(0);"), 0);
+
+        let args = Args::try_parse_from(["glad", "--fuel", "3", "-e", "print 1 + 2;"]).unwrap();
+        let error = run(&args.input, &args).unwrap_err();
+        assert_eq!(describe(&error), "Ran out of fuel.\n[line 1] in script");
+
+        let args =
+            Args::try_parse_from(["glad", "--max-depth", "2", "-e", "print -(-1);"]).unwrap();
+        let error = run(&args.input, &args).unwrap_err();
+        assert_eq!(describe(&error), "Stack overflow.\n[line 1] in script");
+
+        let args = Args::try_parse_from(["glad", "--sandbox", "-e", "print clock();"]).unwrap();
+        let error = run(&args.input, &args).unwrap_err();
+        assert_eq!(
+            describe(&error),
+            "Not allowed to read the clock.\n[line 1] in script"
+        );
+
+        let error = Input {
+            file: Some(PathBuf::from("does/not/exist.lox")),
+            ..Input::default()
+        }
+        .read()
+        .unwrap_err();
+        assert_eq!(exit_code(&error), EXIT_IO_ERROR);
+        assert_eq!(
+            describe(&error),
+            "Error: couldn't read does/not/exist.lox: No such file or directory (os error 2)"
+        );
+    }
+
+    #[test]
+    fn dumps() {
+        assert_eq!(
+            dump_tokens(&Lexer::new("print \"a\";").scan_tokens()),
+            "  1 Print print\n  1 String \"a\" String(\"a\")\n  1 Semicolon ;\n  1 Eof \n"
+        );
+
+        let statements = Parser::new(Lexer::new("var a = 1 + 2;").scan_tokens())
+            .parse()
+            .unwrap();
+        assert_eq!(dump_ast(&statements, false), "(var a (+ 1 2))\n");
+        assert_eq!(dump_ast(&statements, true), "var a = 1 + 2;\n");
+    }
+}
@@ -0,0 +1,976 @@
+use std::{collections::HashMap, ops};
+
+use anyhow::Result;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as LspNotification, PublishDiagnostics,
+    },
+    request::{
+        DocumentSymbolRequest, GotoDefinition, HoverRequest, References, Rename,
+        Request as LspRequest,
+    },
+    Diagnostic, DiagnosticSeverity, DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf, Position,
+    PublishDiagnosticsParams, Range, ReferenceParams, RenameParams, ServerCapabilities, SymbolKind,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::{
+    cst::{self, SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken},
+    interpreter::{RED_ZONE, STACK_SEGMENT},
+    lexer::Lexer,
+    natives,
+    token_type::TokenType,
+};
+
+/// Runs a language server over stdin and stdout until the client asks it to
+/// shut down.
+pub fn serve() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    run(&connection)?;
+    io_threads.join()?;
+
+    Ok(())
+}
+
+fn run(connection: &Connection) -> Result<()> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut server = Server {
+        documents: HashMap::new(),
+    };
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                let response = server.handle_request(request)?;
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                for message in server.handle_notification(notification)? {
+                    connection.sender.send(message)?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+struct Server {
+    documents: HashMap<Url, String>,
+}
+
+impl Server {
+    fn handle_request(&mut self, request: Request) -> Result<Response> {
+        match request.method.as_str() {
+            HoverRequest::METHOD => {
+                let (id, params) = request.extract::<HoverParams>(HoverRequest::METHOD)?;
+                let position = params.text_document_position_params;
+                let hover = self
+                    .documents
+                    .get(&position.text_document.uri)
+                    .and_then(|source| hover(source, position.position));
+
+                Ok(Response::new_ok(id, hover))
+            }
+            GotoDefinition::METHOD => {
+                let (id, params) =
+                    request.extract::<GotoDefinitionParams>(GotoDefinition::METHOD)?;
+                let position = params.text_document_position_params;
+                let uri = position.text_document.uri;
+                let definition = self.documents.get(&uri).and_then(|source| {
+                    let range = definition(source, position.position)?;
+                    Some(GotoDefinitionResponse::Scalar(Location::new(
+                        uri.clone(),
+                        range,
+                    )))
+                });
+
+                Ok(Response::new_ok(id, definition))
+            }
+            References::METHOD => {
+                let (id, params) = request.extract::<ReferenceParams>(References::METHOD)?;
+                let position = params.text_document_position;
+                let uri = position.text_document.uri;
+                let locations = self.documents.get(&uri).map(|source| {
+                    references(
+                        source,
+                        position.position,
+                        params.context.include_declaration,
+                    )
+                    .into_iter()
+                    .map(|range| Location::new(uri.clone(), range))
+                    .collect::<Vec<_>>()
+                });
+
+                Ok(Response::new_ok(id, locations))
+            }
+            DocumentSymbolRequest::METHOD => {
+                let (id, params) =
+                    request.extract::<DocumentSymbolParams>(DocumentSymbolRequest::METHOD)?;
+                let symbols = self
+                    .documents
+                    .get(&params.text_document.uri)
+                    .map(|source| DocumentSymbolResponse::Nested(symbols(source)));
+
+                Ok(Response::new_ok(id, symbols))
+            }
+            Rename::METHOD => {
+                let (id, params) = request.extract::<RenameParams>(Rename::METHOD)?;
+                if !is_identifier(&params.new_name) {
+                    return Ok(Response::new_err(
+                        id,
+                        ErrorCode::InvalidParams as i32,
+                        format!("'{}' is not a valid name.", params.new_name),
+                    ));
+                }
+
+                let position = params.text_document_position;
+                let uri = position.text_document.uri;
+                let edit = self.documents.get(&uri).and_then(|source| {
+                    let edits = rename(source, position.position, &params.new_name)?;
+                    Some(WorkspaceEdit {
+                        changes: Some(HashMap::from([(uri.clone(), edits)])),
+                        ..Default::default()
+                    })
+                });
+
+                Ok(Response::new_ok(id, edit))
+            }
+            _ => Ok(Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request {}", request.method),
+            )),
+        }
+    }
+
+    /// Updates the open documents, returning the diagnostics to publish.
+    fn handle_notification(&mut self, notification: Notification) -> Result<Vec<Message>> {
+        let uri = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = notification
+                    .extract::<<DidOpenTextDocument as LspNotification>::Params>(
+                        DidOpenTextDocument::METHOD,
+                    )?;
+                self.documents
+                    .insert(params.text_document.uri.clone(), params.text_document.text);
+                params.text_document.uri
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = notification
+                    .extract::<<DidChangeTextDocument as LspNotification>::Params>(
+                        DidChangeTextDocument::METHOD,
+                    )?;
+                // We only ask for full syncs, so the last change is the whole text.
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents
+                        .insert(params.text_document.uri.clone(), change.text);
+                }
+                params.text_document.uri
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = notification
+                    .extract::<<DidCloseTextDocument as LspNotification>::Params>(
+                        DidCloseTextDocument::METHOD,
+                    )?;
+                self.documents.remove(&params.text_document.uri);
+                params.text_document.uri
+            }
+            _ => return Ok(Vec::new()),
+        };
+
+        let diagnostics = self
+            .documents
+            .get(&uri)
+            .map(|source| diagnostics(source))
+            .unwrap_or_default();
+
+        Ok(vec![Message::Notification(Notification::new(
+            PublishDiagnostics::METHOD.to_owned(),
+            PublishDiagnosticsParams {
+                uri,
+                diagnostics,
+                version: None,
+            },
+        ))])
+    }
+}
+
+fn diagnostics(source: &str) -> Vec<Diagnostic> {
+    let index = LineIndex::new(source);
+    let parse = cst::parse(source);
+
+    let errors = parse.errors().iter().map(|error| Diagnostic {
+        range: index.range(error.range.clone()),
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("glad".to_owned()),
+        message: error.message.clone(),
+        ..Default::default()
+    });
+
+    // These only fail when they're reached, so they're just warnings.
+    let undefined = Resolution::new(&parse.syntax())
+        .references
+        .into_iter()
+        .filter(|reference| {
+            reference.declaration.is_none() && !natives::names().any(|name| name == reference.name)
+        })
+        .map(|reference| Diagnostic {
+            range: index.range(reference.range),
+            severity: Some(DiagnosticSeverity::WARNING),
+            source: Some("glad".to_owned()),
+            message: format!("Undefined variable '{}'.", reference.name),
+            ..Default::default()
+        });
+
+    errors.chain(undefined).collect()
+}
+
+fn definition(source: &str, position: Position) -> Option<Range> {
+    let index = LineIndex::new(source);
+    let resolution = Resolution::new(&cst::parse(source).syntax());
+    let declaration = resolution.declaration_at(index.offset(position))?;
+
+    Some(index.range(resolution.declarations[declaration].range.clone()))
+}
+
+fn references(source: &str, position: Position, include_declaration: bool) -> Vec<Range> {
+    let index = LineIndex::new(source);
+    let resolution = Resolution::new(&cst::parse(source).syntax());
+
+    match resolution.declaration_at(index.offset(position)) {
+        Some(declaration) => resolution
+            .occurrences(declaration, include_declaration)
+            .into_iter()
+            .map(|range| index.range(range))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+fn rename(source: &str, position: Position, new_name: &str) -> Option<Vec<TextEdit>> {
+    let index = LineIndex::new(source);
+    let resolution = Resolution::new(&cst::parse(source).syntax());
+    let declaration = resolution.declaration_at(index.offset(position))?;
+
+    Some(
+        resolution
+            .occurrences(declaration, true)
+            .into_iter()
+            .map(|range| TextEdit::new(index.range(range), new_name.to_owned()))
+            .collect(),
+    )
+}
+
+/// Whether `name` can be used as the name of a variable.
+fn is_identifier(name: &str) -> bool {
+    match Lexer::new(name).scan_tokens().as_slice() {
+        [token, eof] => {
+            token.token_type == TokenType::Identifier
+                && token.get_lexeme() == name
+                && eof.token_type == TokenType::Eof
+        }
+        _ => false,
+    }
+}
+
+/// The variables and modules declared with `var` and `import`, in the order
+/// they're declared.
+fn symbols(source: &str) -> Vec<DocumentSymbol> {
+    let index = LineIndex::new(source);
+
+    Resolution::new(&cst::parse(source).syntax())
+        .declarations
+        .into_iter()
+        .filter_map(|declaration| {
+            let kind = match declaration.node.kind() {
+                SyntaxKind::VarDecl => SymbolKind::VARIABLE,
+                SyntaxKind::ImportDecl => SymbolKind::MODULE,
+                _ => return None,
+            };
+
+            #[allow(deprecated)]
+            Some(DocumentSymbol {
+                name: declaration.name,
+                detail: None,
+                kind,
+                tags: None,
+                deprecated: None,
+                range: index.range(declaration.node.text_range()),
+                selection_range: index.range(declaration.range),
+                children: None,
+            })
+        })
+        .collect()
+}
+
+fn hover(source: &str, position: Position) -> Option<Hover> {
+    let index = LineIndex::new(source);
+    let root = cst::parse(source).syntax();
+    let token = root.token_at_offset(index.offset(position))?;
+
+    if token.kind().is_trivia() {
+        return None;
+    }
+
+    let node = token.parent();
+    if !matches!(
+        node.kind(),
+        SyntaxKind::Assign
+            | SyntaxKind::Variable
+            | SyntaxKind::Literal
+            | SyntaxKind::Grouping
+            | SyntaxKind::Unary
+            | SyntaxKind::Binary
+            | SyntaxKind::Call
+            | SyntaxKind::Get
+            | SyntaxKind::List
+            | SyntaxKind::Map
+            | SyntaxKind::Index
+    ) {
+        return None;
+    }
+
+    let mut value = format!("```lox\n{}\n```", node.text().trim());
+    if let Some(kind) = infer(&node) {
+        value.push_str(&format!("\n\n{}", kind));
+    }
+    if node.kind() == SyntaxKind::Variable {
+        let resolution = Resolution::new(&root);
+        let comment = resolution
+            .declaration_at(token.text_range().start)
+            .and_then(|declaration| {
+                let start = resolution.declarations[declaration].node.text_range().start;
+                doc_comment(source, start)
+            });
+        if let Some(comment) = comment {
+            value.push_str(&format!("\n\n{}", comment));
+        }
+    }
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: Some(index.range(node.text_range())),
+    })
+}
+
+/// Works out the type an expression will evaluate to, where that's possible
+/// without running it.
+fn infer(node: &SyntaxNode) -> Option<&'static str> {
+    let children = node.children();
+
+    match node.kind() {
+        SyntaxKind::Literal => match first_token(node)? {
+            TokenType::Number => Some("number"),
+            TokenType::String => Some("string"),
+            TokenType::True | TokenType::False => Some("boolean"),
+            TokenType::Nil => Some("nil"),
+            _ => None,
+        },
+        SyntaxKind::Grouping => infer(children.first()?),
+        SyntaxKind::List => Some("list"),
+        SyntaxKind::Map => Some("map"),
+        SyntaxKind::Unary => match first_token(node)? {
+            TokenType::Minus => Some("number"),
+            TokenType::Bang => Some("boolean"),
+            _ => None,
+        },
+        SyntaxKind::Binary => match first_token(node)? {
+            TokenType::Minus | TokenType::Slash | TokenType::Star | TokenType::Percent => {
+                Some("number")
+            }
+            TokenType::Plus => match (infer(children.first()?), infer(children.last()?)) {
+                (Some("number"), Some("number")) => Some("number"),
+                (Some("string"), Some("string")) => Some("string"),
+                _ => None,
+            },
+            _ => Some("boolean"),
+        },
+        _ => None,
+    }
+}
+
+/// The type of the first significant token directly inside `node`, which for
+/// operators is the operator itself.
+fn first_token(node: &SyntaxNode) -> Option<TokenType> {
+    node.children_with_tokens()
+        .into_iter()
+        .find_map(|child| match child {
+            SyntaxElement::Token(token) if !token.kind().is_trivia() => match token.kind() {
+                SyntaxKind::Token(token_type) => Some(token_type),
+                _ => None,
+            },
+            _ => None,
+        })
+}
+
+/// The comment lines directly above the line `offset` is on, without their
+/// slashes.
+fn doc_comment(source: &str, offset: usize) -> Option<String> {
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let mut lines: Vec<_> = source[..line_start]
+        .lines()
+        .rev()
+        .map_while(|line| line.trim().strip_prefix("//"))
+        .map(str::trim)
+        .collect();
+
+    if lines.is_empty() {
+        return None;
+    }
+    lines.reverse();
+    Some(lines.join("\n"))
+}
+
+/// A variable or module declared in a document.
+struct Declaration {
+    name: String,
+    /// Byte range of the name where it's declared.
+    range: ops::Range<usize>,
+    /// The `VarDecl`, `ImportDecl` or `ForStmt` that declares it.
+    node: SyntaxNode,
+}
+
+/// A use of a variable.
+struct Reference {
+    name: String,
+    range: ops::Range<usize>,
+    /// Index of the declaration the name refers to, if it's declared in the
+    /// document.
+    declaration: Option<usize>,
+}
+
+/// Which declaration each variable in a document refers to.
+///
+/// This follows the scoping of the interpreter: blocks and `for-in` bodies
+/// get scopes of their own, and a variable only exists from the statement
+/// declaring it on. As programs can't define functions, where a name is
+/// used in the source is the order it's used in when run.
+#[derive(Default)]
+struct Resolution {
+    declarations: Vec<Declaration>,
+    references: Vec<Reference>,
+}
+
+impl Resolution {
+    fn new(root: &SyntaxNode) -> Self {
+        let mut resolver = Resolver {
+            scopes: vec![HashMap::new()],
+            resolution: Self::default(),
+        };
+        resolver.node(root);
+        resolver.resolution
+    }
+
+    /// The declaration of the name at `offset`, either where it's declared
+    /// or where it's used.
+    fn declaration_at(&self, offset: usize) -> Option<usize> {
+        // A cursor just after a name is still on it.
+        let touches = |range: &ops::Range<usize>| range.start <= offset && offset <= range.end;
+
+        self.declarations
+            .iter()
+            .position(|declaration| touches(&declaration.range))
+            .or_else(|| {
+                self.references
+                    .iter()
+                    .find(|reference| touches(&reference.range))?
+                    .declaration
+            })
+    }
+
+    /// Where the declaration is used, optionally along with where it's
+    /// declared, in source order.
+    fn occurrences(&self, declaration: usize, include_declaration: bool) -> Vec<ops::Range<usize>> {
+        let mut ranges: Vec<_> = self
+            .references
+            .iter()
+            .filter(|reference| reference.declaration == Some(declaration))
+            .map(|reference| reference.range.clone())
+            .collect();
+        if include_declaration {
+            ranges.push(self.declarations[declaration].range.clone());
+        }
+
+        ranges.sort_by_key(|range| range.start);
+        ranges
+    }
+}
+
+struct Resolver {
+    /// The names in each enclosing scope, innermost last, with the index of
+    /// their declarations.
+    scopes: Vec<HashMap<String, usize>>,
+    resolution: Resolution,
+}
+
+impl Resolver {
+    fn node(&mut self, node: &SyntaxNode) {
+        match node.kind() {
+            SyntaxKind::Block => {
+                self.scopes.push(HashMap::new());
+                self.children(node);
+                self.scopes.pop();
+            }
+            // An initializer still sees any variable the declaration shadows.
+            SyntaxKind::VarDecl | SyntaxKind::ImportDecl => {
+                self.children(node);
+                self.declare(node);
+            }
+            // The iterable is evaluated outside the scope of the loop
+            // variable, and the body inside it.
+            SyntaxKind::ForStmt => {
+                let mut children = node.children();
+                let body = children.pop();
+                for child in &children {
+                    self.node(child);
+                }
+
+                self.scopes.push(HashMap::new());
+                self.declare(node);
+                if let Some(body) = body {
+                    self.node(&body);
+                }
+                self.scopes.pop();
+            }
+            SyntaxKind::Variable => {
+                if let Some(name) = identifier(node) {
+                    let declaration = self
+                        .scopes
+                        .iter()
+                        .rev()
+                        .find_map(|scope| scope.get(name.text()).copied());
+                    self.resolution.references.push(Reference {
+                        name: name.text().to_owned(),
+                        range: name.text_range(),
+                        declaration,
+                    });
+                }
+            }
+            _ => self.children(node),
+        }
+    }
+
+    fn children(&mut self, node: &SyntaxNode) {
+        for child in node.children() {
+            // Nesting is limited by the parser, but the tree is still deep
+            // enough to need more stack than a thread might start with.
+            stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || self.node(&child));
+        }
+    }
+
+    fn declare(&mut self, node: &SyntaxNode) {
+        let name = match identifier(node) {
+            Some(name) => name,
+            None => return,
+        };
+
+        let index = self.resolution.declarations.len();
+        self.resolution.declarations.push(Declaration {
+            name: name.text().to_owned(),
+            range: name.text_range(),
+            node: node.clone(),
+        });
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.text().to_owned(), index);
+        }
+    }
+}
+
+/// The first identifier directly inside `node`, which is the name it
+/// declares or uses.
+fn identifier(node: &SyntaxNode) -> Option<SyntaxToken> {
+    node.children_with_tokens()
+        .into_iter()
+        .find_map(|child| match child {
+            SyntaxElement::Token(token)
+                if token.kind() == SyntaxKind::Token(TokenType::Identifier) =>
+            {
+                Some(token)
+            }
+            _ => None,
+        })
+}
+
+/// Converts between byte offsets and LSP positions, which count lines and
+/// UTF-16 code units.
+struct LineIndex<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let character = self.source[self.line_starts[line]..offset]
+            .encode_utf16()
+            .count();
+
+        Position::new(line as u32, character as u32)
+    }
+
+    fn range(&self, range: std::ops::Range<usize>) -> Range {
+        Range::new(self.position(range.start), self.position(range.end))
+    }
+
+    fn offset(&self, position: Position) -> usize {
+        let start = match self.line_starts.get(position.line as usize) {
+            Some(start) => *start,
+            None => return self.source.len(),
+        };
+
+        let mut units = 0;
+        for (i, c) in self.source[start..].char_indices() {
+            if units >= position.character as usize || c == '\n' {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+
+        self.source.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use lsp_server::RequestId;
+    use lsp_types::{
+        request::Initialize, DidOpenTextDocumentParams, InitializeParams, TextDocumentIdentifier,
+        TextDocumentItem, TextDocumentPositionParams,
+    };
+    use std::thread;
+
+    fn uri() -> Url {
+        Url::parse("file:///test.lox").unwrap()
+    }
+
+    fn hover_text(source: &str, character: u32) -> Option<String> {
+        match hover(source, Position::new(0, character))?.contents {
+            HoverContents::Markup(markup) => Some(markup.value),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn line_index() {
+        let index = LineIndex::new("\"é\" +\n𝔸");
+
+        assert_eq!(index.position(5), Position::new(0, 4));
+        assert_eq!(index.position(7), Position::new(1, 0));
+        assert_eq!(index.position(11), Position::new(1, 2));
+        assert_eq!(index.offset(Position::new(0, 4)), 5);
+        assert_eq!(index.offset(Position::new(1, 2)), 11);
+        assert_eq!(index.offset(Position::new(0, 99)), 6);
+    }
+
+    #[test]
+    fn hover_infers_kinds() {
+        assert_eq!(
+            hover_text("1 + (2 * 3);", 2).unwrap(),
+            "```lox\n1 + (2 * 3)\n```\n\nnumber"
+        );
+        assert_eq!(
+            hover_text("\"a\" + \"b\" == nil;", 11).unwrap(),
+            "```lox\n\"a\" + \"b\" == nil\n```\n\nboolean"
+        );
+        assert_eq!(
+            hover_text("\"a\" + 1;", 4).unwrap(),
+            "```lox\n\"a\" + 1\n```"
+        );
+        assert_eq!(hover_text("1 + 2;", 1), None);
+        assert_eq!(hover_text("var a = 1;", 4), None);
+    }
+
+    fn range(line: u32, start: u32, end: u32) -> Range {
+        Range::new(Position::new(line, start), Position::new(line, end))
+    }
+
+    #[test]
+    fn resolves_scopes() {
+        let source = "var a = 1;\n{ var a = a; print a; }\nfor (var a in [a]) a;\nprint a;";
+
+        assert_eq!(
+            definition(source, Position::new(0, 4)),
+            Some(range(0, 4, 5))
+        );
+        assert_eq!(
+            definition(source, Position::new(1, 10)),
+            Some(range(0, 4, 5))
+        );
+        assert_eq!(
+            definition(source, Position::new(1, 19)),
+            Some(range(1, 6, 7))
+        );
+        assert_eq!(
+            definition(source, Position::new(2, 15)),
+            Some(range(0, 4, 5))
+        );
+        assert_eq!(
+            definition(source, Position::new(2, 19)),
+            Some(range(2, 9, 10))
+        );
+        assert_eq!(
+            definition(source, Position::new(3, 6)),
+            Some(range(0, 4, 5))
+        );
+        assert_eq!(definition(source, Position::new(1, 0)), None);
+        assert_eq!(definition("print clock;", Position::new(0, 7)), None);
+
+        assert_eq!(
+            references(source, Position::new(0, 4), false),
+            vec![range(1, 10, 11), range(2, 15, 16), range(3, 6, 7)]
+        );
+        assert_eq!(
+            references(source, Position::new(2, 9), true),
+            vec![range(2, 9, 10), range(2, 19, 20)]
+        );
+    }
+
+    #[test]
+    fn renames() {
+        let source = "import \"m.lox\" as m;\nm.f(m);\n";
+        let edits = rename(source, Position::new(1, 0), "module").unwrap();
+        assert_eq!(
+            edits
+                .iter()
+                .map(|edit| (edit.range, edit.new_text.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (range(0, 18, 19), "module"),
+                (range(1, 0, 1), "module"),
+                (range(1, 4, 5), "module")
+            ]
+        );
+        assert_eq!(rename(source, Position::new(0, 8), "x"), None);
+
+        assert!(is_identifier("camelCase2"));
+        assert!(!is_identifier("var"));
+        assert!(!is_identifier("a b"));
+        assert!(!is_identifier("a_b"));
+        assert!(!is_identifier(""));
+    }
+
+    #[test]
+    fn document_symbols() {
+        let symbols = symbols("var a;\nimport \"m.lox\" as m;\nfor (var x in []) { var b = x; }");
+        assert_eq!(
+            symbols
+                .iter()
+                .map(|symbol| (symbol.name.as_str(), symbol.kind, symbol.selection_range))
+                .collect::<Vec<_>>(),
+            vec![
+                ("a", SymbolKind::VARIABLE, range(0, 4, 5)),
+                ("m", SymbolKind::MODULE, range(1, 18, 19)),
+                ("b", SymbolKind::VARIABLE, range(2, 24, 25)),
+            ]
+        );
+        assert_eq!(symbols[0].range, range(0, 0, 6));
+    }
+
+    #[test]
+    fn undefined_variables() {
+        let diagnostics = diagnostics("print a;\nvar a = b + PI;\n{ var c; }\nprint c + a;");
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (
+                    diagnostic.range,
+                    diagnostic.severity,
+                    diagnostic.message.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    range(0, 6, 7),
+                    Some(DiagnosticSeverity::WARNING),
+                    "Undefined variable 'a'."
+                ),
+                (
+                    range(1, 8, 9),
+                    Some(DiagnosticSeverity::WARNING),
+                    "Undefined variable 'b'."
+                ),
+                (
+                    range(3, 6, 7),
+                    Some(DiagnosticSeverity::WARNING),
+                    "Undefined variable 'c'."
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn hover_shows_doc_comments() {
+        let source = "// The answer.\n// Really.\nvar a = 42;\nprint a;";
+        match hover(source, Position::new(3, 6)).unwrap().contents {
+            HoverContents::Markup(markup) => {
+                assert_eq!(markup.value, "```lox\na\n```\n\nThe answer.\nReally.")
+            }
+            contents => panic!("unexpected {:?}", contents),
+        }
+        assert_eq!(hover_text("var a; a;", 7).unwrap(), "```lox\na\n```");
+    }
+
+    #[test]
+    fn session() {
+        let (server, client) = Connection::memory();
+        let server = thread::spawn(move || run(&server).unwrap());
+
+        #[allow(deprecated)]
+        let initialize = InitializeParams {
+            root_uri: None,
+            ..Default::default()
+        };
+        client
+            .sender
+            .send(
+                Request::new(
+                    RequestId::from(1),
+                    Initialize::METHOD.to_owned(),
+                    initialize,
+                )
+                .into(),
+            )
+            .unwrap();
+        client.receiver.recv().unwrap();
+        client
+            .sender
+            .send(Notification::new("initialized".to_owned(), serde_json::json!({})).into())
+            .unwrap();
+
+        client
+            .sender
+            .send(
+                Notification::new(
+                    DidOpenTextDocument::METHOD.to_owned(),
+                    DidOpenTextDocumentParams {
+                        text_document: TextDocumentItem::new(
+                            uri(),
+                            "lox".to_owned(),
+                            1,
+                            "(1 +".to_owned(),
+                        ),
+                    },
+                )
+                .into(),
+            )
+            .unwrap();
+
+        match client.receiver.recv().unwrap() {
+            Message::Notification(notification) => {
+                let params: PublishDiagnosticsParams =
+                    serde_json::from_value(notification.params).unwrap();
+                let messages: Vec<_> = params
+                    .diagnostics
+                    .iter()
+                    .map(|d| d.message.as_str())
+                    .collect();
+                assert_eq!(
+                    messages,
+                    vec![
+                        "Expect expression.",
+                        "Expect ')' after expression.",
+                        "Expect ';' after expression."
+                    ]
+                );
+                assert_eq!(params.diagnostics[0].range.start, Position::new(0, 4));
+            }
+            message => panic!("unexpected {:?}", message),
+        }
+
+        client
+            .sender
+            .send(
+                Request::new(
+                    RequestId::from(2),
+                    HoverRequest::METHOD.to_owned(),
+                    HoverParams {
+                        text_document_position_params: TextDocumentPositionParams::new(
+                            TextDocumentIdentifier::new(uri()),
+                            Position::new(0, 1),
+                        ),
+                        work_done_progress_params: Default::default(),
+                    },
+                )
+                .into(),
+            )
+            .unwrap();
+
+        match client.receiver.recv().unwrap() {
+            Message::Response(response) => {
+                let hover: Hover = serde_json::from_value(response.result.unwrap()).unwrap();
+                assert_eq!(hover.range.unwrap().start, Position::new(0, 1));
+            }
+            message => panic!("unexpected {:?}", message),
+        }
+
+        client
+            .sender
+            .send(
+                Request::new(
+                    RequestId::from(3),
+                    DocumentSymbolRequest::METHOD.to_owned(),
+                    DocumentSymbolParams {
+                        text_document: TextDocumentIdentifier::new(uri()),
+                        work_done_progress_params: Default::default(),
+                        partial_result_params: Default::default(),
+                    },
+                )
+                .into(),
+            )
+            .unwrap();
+
+        match client.receiver.recv().unwrap() {
+            Message::Response(response) => {
+                assert_eq!(response.result, Some(serde_json::json!([])));
+            }
+            message => panic!("unexpected {:?}", message),
+        }
+
+        client
+            .sender
+            .send(Request::new(RequestId::from(4), "shutdown".to_owned(), ()).into())
+            .unwrap();
+        client.receiver.recv().unwrap();
+        client
+            .sender
+            .send(Notification::new("exit".to_owned(), ()).into())
+            .unwrap();
+
+        server.join().unwrap();
+    }
+}
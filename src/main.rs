@@ -1,44 +1,33 @@
-use std::io::{self, BufRead};
-use std::{fs::read_to_string, io::Write};
+use std::process;
 
 use anyhow::Result;
 use clap::Parser;
-use glad::{run, Args};
+use glad::{
+    ast, check, describe, exit_code, exit_status, fmt, lsp, repl, run, tokens, Args, Command,
+};
 
-fn main() -> Result<()> {
+fn main() {
     let args = Args::parse();
 
-    match args.file {
-        Some(file_path) => {
-            let file_contents = read_to_string(file_path)?;
-            run(&file_contents)?;
-        }
-        None => {
-            let mut input = String::new();
-            let mut stdin = io::stdin().lock();
-            let mut stdout = io::stdout();
-
-            loop {
-                print!("> ");
-                stdout.flush()?;
-
-                input.clear();
-                match stdin.read_line(&mut input) {
-                    Ok(bytes_read) => {
-                        if bytes_read == 0 {
-                            std::process::exit(0);
-                        } else {
-                            run(&input)?
-                        }
-                    }
-                    Err(e) => {
-                        println!("Error: {}", e);
-                        std::process::exit(70);
-                    }
-                }
-            }
+    if let Err(error) = dispatch(&args) {
+        // A program that called exit() has nothing more to say.
+        if exit_status(&error).is_none() {
+            eprintln!("{}", describe(&error));
         }
+        process::exit(exit_code(&error));
     }
+}
 
-    Ok(())
+fn dispatch(args: &Args) -> Result<()> {
+    match &args.command {
+        Some(Command::Run { input }) => run(input, args),
+        Some(Command::Repl) => repl::start(args),
+        Some(Command::Check { input }) => check(&input.read()?),
+        Some(Command::Tokens { input }) => tokens(&input.read()?),
+        Some(Command::Ast { input }) => ast(&input.read()?, args),
+        Some(Command::Fmt { files, check }) => fmt(files, *check),
+        Some(Command::Lsp) => lsp::serve(),
+        None if args.input.is_empty() => repl::start(args),
+        None => run(&args.input, args),
+    }
 }
@@ -0,0 +1,183 @@
+//! Functions built into the interpreter, both those defined as globals and
+//! the methods of built-in types.
+
+mod io;
+mod list;
+mod map;
+mod math;
+mod string;
+
+use std::{mem, rc::Rc};
+
+use crate::{
+    environment::Environment,
+    interpreter::{ErrorKind, Interpreter, RuntimeError},
+    object::{Key, Native, Object},
+};
+
+/// What each element of a list or entry of a map counts for against the
+/// allocation limit.
+pub const ELEMENT_SIZE: usize = mem::size_of::<Object>();
+pub const ENTRY_SIZE: usize = mem::size_of::<(Key, Object)>();
+
+/// The largest integer up to which every integer is exactly a number.
+const MAX_INTEGER: f32 = 16_777_216.0;
+
+const GLOBALS: &[Native] = &[
+    Native {
+        name: "clock",
+        arity: 0,
+        function: clock,
+    },
+    Native {
+        name: "range",
+        arity: 2,
+        function: range,
+    },
+    Native {
+        name: "str",
+        arity: 1,
+        function: str,
+    },
+    Native {
+        name: "num",
+        arity: 1,
+        function: num,
+    },
+];
+
+pub fn define(environment: &mut Environment) {
+    for native in GLOBALS.iter().chain(math::GLOBALS).chain(io::GLOBALS) {
+        environment.define(
+            native.name.to_owned(),
+            Object::Native(Rc::new(native.clone())),
+        );
+    }
+    for (name, value) in math::CONSTANTS {
+        environment.define(name.to_string(), Object::Number(*value));
+    }
+}
+
+/// The names `define` gives values to.
+pub fn names() -> impl Iterator<Item = &'static str> {
+    GLOBALS
+        .iter()
+        .chain(math::GLOBALS)
+        .chain(io::GLOBALS)
+        .map(|native| native.name)
+        .chain(math::CONSTANTS.iter().map(|(name, _)| *name))
+}
+
+/// The methods that can be called on `receiver`.
+pub fn methods(receiver: &Object) -> &'static [Native] {
+    match receiver {
+        Object::List(_) => list::METHODS,
+        Object::Map(_) => map::METHODS,
+        Object::String(_) => string::METHODS,
+        _ => &[],
+    }
+}
+
+/// The method called `name` on `receiver`, if it has one.
+pub fn method(receiver: &Object, name: &str) -> Option<&'static Native> {
+    methods(receiver).iter().find(|method| method.name == name)
+}
+
+/// Converts an index into something `len` elements long.
+pub fn index(value: &Object, len: usize) -> Result<usize, RuntimeError> {
+    let index = match value {
+        Object::Number(index) if index.fract() == 0.0 => *index,
+        _ => return Err(ErrorKind::IndexType.into()),
+    };
+
+    if index < 0.0 || index >= len as f32 {
+        return Err(ErrorKind::IndexOutOfBounds { index, len }.into());
+    }
+
+    Ok(index as usize)
+}
+
+/// Creates a list, accounting for its elements against the allocation limit.
+pub fn new_list(interpreter: &Interpreter, elements: Vec<Object>) -> Result<Object, RuntimeError> {
+    interpreter.allocate(elements.len() * ELEMENT_SIZE)?;
+    Ok(Object::list(elements))
+}
+
+/// Creates a string, accounting for it against the allocation limit.
+pub fn new_string(interpreter: &Interpreter, string: String) -> Result<Object, RuntimeError> {
+    interpreter.allocate(string.len())?;
+    Ok(Object::String(string.into()))
+}
+
+/// The number an argument holds, or an error if it isn't one.
+pub fn number(value: &Object) -> Result<f32, RuntimeError> {
+    match value {
+        Object::Number(number) => Ok(*number),
+        _ => Err(ErrorKind::ArgumentType {
+            expected: "a number",
+        }
+        .into()),
+    }
+}
+
+/// The string an argument holds, or an error if it isn't one.
+pub fn string(value: &Object) -> Result<&str, RuntimeError> {
+    match value {
+        Object::String(string) => Ok(string),
+        _ => Err(ErrorKind::ArgumentType {
+            expected: "a string",
+        }
+        .into()),
+    }
+}
+
+/// Converts a value to be used as a key in a map.
+pub fn key(value: &Object) -> Result<Key, RuntimeError> {
+    Key::new(value).ok_or_else(|| ErrorKind::KeyType.into())
+}
+
+/// Seconds since the interpreter started. Numbers are too coarse to count
+/// from the Unix epoch with any useful precision.
+fn clock(interpreter: &Interpreter, _: &[Object]) -> Result<Object, RuntimeError> {
+    interpreter.require(interpreter.capabilities.clock, "read the clock")?;
+    Ok(Object::Number(interpreter.started.elapsed().as_secs_f32()))
+}
+
+/// The integers from the first argument up to but not including the second.
+/// Both must be small enough that every integer in between is a number.
+fn range(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let bound = |value: &Object| match value {
+        Object::Number(number) if number.fract() == 0.0 && number.abs() <= MAX_INTEGER => {
+            Ok(*number as i64)
+        }
+        _ => Err(RuntimeError::from(ErrorKind::ArgumentType {
+            expected: "an integer from -16777216 to 16777216",
+        })),
+    };
+    let (start, end) = (bound(&arguments[0])?, bound(&arguments[1])?);
+
+    // Account for the list before building it, so a huge range fails
+    // rather than eating all the memory there is.
+    let len = end.saturating_sub(start).max(0) as usize;
+    interpreter.allocate(len.saturating_mul(ELEMENT_SIZE))?;
+    Ok(Object::list(
+        (start..end).map(|n| Object::Number(n as f32)).collect(),
+    ))
+}
+
+/// The value as it would be printed.
+fn str(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    match &arguments[0] {
+        Object::String(string) => Ok(Object::String(string.clone())),
+        value => new_string(interpreter, value.to_string()),
+    }
+}
+
+/// The number a string spells out, or nil if it doesn't.
+fn num(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let number = string(&arguments[0])?.trim().parse::<f32>();
+    Ok(match number {
+        Ok(number) if number.is_finite() => Object::Number(number),
+        _ => Object::Nil,
+    })
+}
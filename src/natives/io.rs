@@ -0,0 +1,171 @@
+//! Functions that reach outside the interpreter, to files, the process and
+//! its environment, defined as globals. Each checks the interpreter's
+//! capabilities first.
+
+use std::{env, fs, io, path::Path};
+
+use super::{new_list, new_string, string};
+use crate::{
+    interpreter::{ErrorKind, Interpreter, RuntimeError},
+    object::{Native, Object},
+};
+
+pub const GLOBALS: &[Native] = &[
+    Native {
+        name: "readFile",
+        arity: 1,
+        function: read_file,
+    },
+    Native {
+        name: "writeFile",
+        arity: 2,
+        function: write_file,
+    },
+    Native {
+        name: "listDir",
+        arity: 1,
+        function: list_dir,
+    },
+    Native {
+        name: "readLine",
+        arity: 0,
+        function: read_line,
+    },
+    Native {
+        name: "args",
+        arity: 0,
+        function: args,
+    },
+    Native {
+        name: "env",
+        arity: 1,
+        function: env,
+    },
+    Native {
+        name: "exit",
+        arity: 1,
+        function: exit,
+    },
+];
+
+fn io_error(action: &'static str, path: &str, error: io::Error) -> RuntimeError {
+    ErrorKind::Io {
+        action,
+        path: path.to_owned(),
+        error,
+    }
+    .into()
+}
+
+/// The whole of a file, which must be UTF-8.
+fn read_file(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let path = string(&arguments[0])?;
+    interpreter.require(
+        interpreter.capabilities.can_read(Path::new(path)),
+        &format!("read '{}'", path),
+    )?;
+
+    let contents = fs::read_to_string(path).map_err(|error| io_error("read", path, error))?;
+    new_string(interpreter, contents)
+}
+
+/// Replaces the contents of a file, creating it if it doesn't exist.
+fn write_file(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let path = string(&arguments[0])?;
+    let contents = string(&arguments[1])?;
+    interpreter.require(
+        interpreter.capabilities.can_write(Path::new(path)),
+        &format!("write '{}'", path),
+    )?;
+
+    fs::write(path, contents).map_err(|error| io_error("write", path, error))?;
+    Ok(Object::Nil)
+}
+
+/// The names of the entries in a directory, sorted.
+fn list_dir(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let path = string(&arguments[0])?;
+    interpreter.require(
+        interpreter.capabilities.can_read(Path::new(path)),
+        &format!("read '{}'", path),
+    )?;
+
+    let mut names = fs::read_dir(path)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned()))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|error| io_error("list", path, error))?;
+    names.sort();
+
+    interpreter.allocate(names.iter().map(String::len).sum())?;
+    new_list(
+        interpreter,
+        names
+            .into_iter()
+            .map(|name| Object::String(name.into()))
+            .collect(),
+    )
+}
+
+/// The next line of input without its line ending, or nil at the end.
+fn read_line(interpreter: &Interpreter, _: &[Object]) -> Result<Object, RuntimeError> {
+    interpreter.require(interpreter.capabilities.stdin, "read from stdin")?;
+
+    let mut line = String::new();
+    let read = interpreter
+        .input
+        .borrow_mut()
+        .read_line(&mut line)
+        .map_err(|error| io_error("read", "stdin", error))?;
+    if read == 0 {
+        return Ok(Object::Nil);
+    }
+
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    new_string(interpreter, line)
+}
+
+/// The arguments given to the program, such as those after the script on
+/// the command line.
+fn args(interpreter: &Interpreter, _: &[Object]) -> Result<Object, RuntimeError> {
+    let args = interpreter
+        .args
+        .iter()
+        .map(|arg| Object::String(arg.as_str().into()))
+        .collect();
+    new_list(interpreter, args)
+}
+
+/// The value of an environment variable, or nil if it isn't set.
+fn env(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let name = string(&arguments[0])?;
+    interpreter.require(interpreter.capabilities.env, "read the environment")?;
+
+    match env::var(name) {
+        Ok(value) => new_string(interpreter, value),
+        Err(_) => Ok(Object::Nil),
+    }
+}
+
+/// Stops the program with the given exit status, from 0 to 255 as that's
+/// all a process can portably exit with. This unwinds like an error, so
+/// whatever is running the program decides what to do with it.
+fn exit(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    match arguments[0] {
+        Object::Number(code) if (0.0..=255.0).contains(&code) && code.fract() == 0.0 => {
+            Err(ErrorKind::Exit(code as i32).into())
+        }
+        Object::Number(code) if code.fract() == 0.0 => Err(ErrorKind::ExitCode.into()),
+        _ => Err(ErrorKind::ArgumentType {
+            expected: "an integer",
+        }
+        .into()),
+    }
+}
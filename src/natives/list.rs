@@ -0,0 +1,170 @@
+//! Methods of lists. Each is passed the list as its first argument.
+
+use std::{cmp::Ordering, rc::Rc};
+
+use super::{index, new_list, new_string, string, ELEMENT_SIZE};
+use crate::{
+    interpreter::{ErrorKind, Interpreter, RuntimeError},
+    object::{Collection, Native, Object},
+};
+
+pub const METHODS: &[Native] = &[
+    Native {
+        name: "push",
+        arity: 1,
+        function: push,
+    },
+    Native {
+        name: "pop",
+        arity: 0,
+        function: pop,
+    },
+    Native {
+        name: "len",
+        arity: 0,
+        function: len,
+    },
+    Native {
+        name: "insert",
+        arity: 2,
+        function: insert,
+    },
+    Native {
+        name: "remove",
+        arity: 1,
+        function: remove,
+    },
+    Native {
+        name: "slice",
+        arity: 2,
+        function: slice,
+    },
+    Native {
+        name: "map",
+        arity: 1,
+        function: map,
+    },
+    Native {
+        name: "filter",
+        arity: 1,
+        function: filter,
+    },
+    Native {
+        name: "sort",
+        arity: 0,
+        function: sort,
+    },
+    Native {
+        name: "join",
+        arity: 1,
+        function: join,
+    },
+];
+
+fn list(receiver: &Object) -> &Rc<Collection<Vec<Object>>> {
+    match receiver {
+        Object::List(list) => list,
+        _ => unreachable!("list method called on {}", receiver),
+    }
+}
+
+fn push(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    interpreter.allocate(ELEMENT_SIZE)?;
+    list(&arguments[0]).borrow_mut().push(arguments[1].clone());
+    Ok(Object::Nil)
+}
+
+fn pop(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    list(&arguments[0])
+        .borrow_mut()
+        .pop()
+        .ok_or_else(|| ErrorKind::EmptyList.into())
+}
+
+fn len(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    Ok(Object::Number(list(&arguments[0]).borrow().len() as f32))
+}
+
+fn insert(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let mut list = list(&arguments[0]).borrow_mut();
+    // Inserting at the end is allowed.
+    let index = index(&arguments[1], list.len() + 1)?;
+    interpreter.allocate(ELEMENT_SIZE)?;
+    list.insert(index, arguments[2].clone());
+    Ok(Object::Nil)
+}
+
+fn remove(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let mut list = list(&arguments[0]).borrow_mut();
+    let index = index(&arguments[1], list.len())?;
+    Ok(list.remove(index))
+}
+
+/// The elements from `start` up to but not including `end`.
+fn slice(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let elements = {
+        let list = list(&arguments[0]).borrow();
+        let start = index(&arguments[1], list.len() + 1)?;
+        let end = index(&arguments[2], list.len() + 1)?;
+        list[start..end.max(start)].to_vec()
+    };
+    new_list(interpreter, elements)
+}
+
+// Callbacks are run over a copy of the list, so they can change the list
+// itself without upsetting the iteration.
+
+fn map(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let elements = list(&arguments[0]).borrow().clone();
+    let elements = elements
+        .into_iter()
+        .map(|element| interpreter.call(arguments[1].clone(), &[element]))
+        .collect::<Result<_, _>>()?;
+    new_list(interpreter, elements)
+}
+
+fn filter(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let elements = list(&arguments[0]).borrow().clone();
+    let mut kept = Vec::new();
+    for element in elements {
+        let keep = interpreter.call(arguments[1].clone(), std::slice::from_ref(&element))?;
+        if interpreter.is_truthy(keep) {
+            kept.push(element);
+        }
+    }
+    new_list(interpreter, kept)
+}
+
+/// Sorts numbers or strings in place, in ascending order.
+fn sort(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let mut list = list(&arguments[0]).borrow_mut();
+
+    let numbers = list
+        .iter()
+        .all(|element| matches!(element, Object::Number(_)));
+    let strings = list
+        .iter()
+        .all(|element| matches!(element, Object::String(_)));
+    if !numbers && !strings {
+        return Err(ErrorKind::Unsortable.into());
+    }
+
+    list.sort_by(|a, b| match (a, b) {
+        (Object::Number(a), Object::Number(b)) => a.total_cmp(b),
+        (Object::String(a), Object::String(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    });
+    Ok(Object::Nil)
+}
+
+/// The elements as they'd be printed, with the argument between each one.
+fn join(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let separator = string(&arguments[1])?;
+    let joined = list(&arguments[0])
+        .borrow()
+        .iter()
+        .map(|element| element.to_string())
+        .collect::<Vec<_>>()
+        .join(separator);
+    new_string(interpreter, joined)
+}
@@ -0,0 +1,78 @@
+//! Methods of maps. Each is passed the map as its first argument.
+
+use std::rc::Rc;
+
+use indexmap::IndexMap;
+
+use super::{key, new_list};
+use crate::{
+    interpreter::{Interpreter, RuntimeError},
+    object::{Collection, Key, Native, Object},
+};
+
+pub const METHODS: &[Native] = &[
+    Native {
+        name: "has",
+        arity: 1,
+        function: has,
+    },
+    Native {
+        name: "remove",
+        arity: 1,
+        function: remove,
+    },
+    Native {
+        name: "keys",
+        arity: 0,
+        function: keys,
+    },
+    Native {
+        name: "values",
+        arity: 0,
+        function: values,
+    },
+    Native {
+        name: "len",
+        arity: 0,
+        function: len,
+    },
+];
+
+fn map(receiver: &Object) -> &Rc<Collection<IndexMap<Key, Object>>> {
+    match receiver {
+        Object::Map(map) => map,
+        _ => unreachable!("map method called on {}", receiver),
+    }
+}
+
+fn has(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let key = key(&arguments[1])?;
+    Ok(Object::Bool(map(&arguments[0]).borrow().contains_key(&key)))
+}
+
+/// Removes an entry, returning its value, or nil if there wasn't one.
+fn remove(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let key = key(&arguments[1])?;
+    Ok(map(&arguments[0])
+        .borrow_mut()
+        .shift_remove(&key)
+        .unwrap_or(Object::Nil))
+}
+
+fn keys(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let keys: Vec<Object> = map(&arguments[0])
+        .borrow()
+        .keys()
+        .map(Key::to_object)
+        .collect();
+    new_list(interpreter, keys)
+}
+
+fn values(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let values: Vec<Object> = map(&arguments[0]).borrow().values().cloned().collect();
+    new_list(interpreter, values)
+}
+
+fn len(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    Ok(Object::Number(map(&arguments[0]).borrow().len() as f32))
+}
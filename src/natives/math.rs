@@ -0,0 +1,231 @@
+//! Mathematical functions and constants, defined as globals.
+
+use std::f32::consts;
+
+use super::number;
+use crate::{
+    interpreter::{Interpreter, RuntimeError},
+    object::{Native, Object},
+};
+
+pub const CONSTANTS: &[(&str, f32)] = &[("PI", consts::PI), ("E", consts::E)];
+
+pub const GLOBALS: &[Native] = &[
+    Native {
+        name: "floor",
+        arity: 1,
+        function: floor,
+    },
+    Native {
+        name: "ceil",
+        arity: 1,
+        function: ceil,
+    },
+    Native {
+        name: "round",
+        arity: 1,
+        function: round,
+    },
+    Native {
+        name: "abs",
+        arity: 1,
+        function: abs,
+    },
+    Native {
+        name: "sqrt",
+        arity: 1,
+        function: sqrt,
+    },
+    Native {
+        name: "pow",
+        arity: 2,
+        function: pow,
+    },
+    Native {
+        name: "min",
+        arity: 2,
+        function: min,
+    },
+    Native {
+        name: "max",
+        arity: 2,
+        function: max,
+    },
+    Native {
+        name: "sin",
+        arity: 1,
+        function: sin,
+    },
+    Native {
+        name: "cos",
+        arity: 1,
+        function: cos,
+    },
+    Native {
+        name: "tan",
+        arity: 1,
+        function: tan,
+    },
+    Native {
+        name: "asin",
+        arity: 1,
+        function: asin,
+    },
+    Native {
+        name: "acos",
+        arity: 1,
+        function: acos,
+    },
+    Native {
+        name: "atan",
+        arity: 1,
+        function: atan,
+    },
+    Native {
+        name: "atan2",
+        arity: 2,
+        function: atan2,
+    },
+    Native {
+        name: "log",
+        arity: 1,
+        function: log,
+    },
+    Native {
+        name: "exp",
+        arity: 1,
+        function: exp,
+    },
+    Native {
+        name: "random",
+        arity: 0,
+        function: random,
+    },
+    Native {
+        name: "seed",
+        arity: 1,
+        function: seed,
+    },
+];
+
+/// Applies `function` to the only argument.
+fn unary(arguments: &[Object], function: fn(f32) -> f32) -> Result<Object, RuntimeError> {
+    Ok(Object::Number(function(number(&arguments[0])?)))
+}
+
+/// Applies `function` to the two arguments.
+fn binary(arguments: &[Object], function: fn(f32, f32) -> f32) -> Result<Object, RuntimeError> {
+    let (a, b) = (number(&arguments[0])?, number(&arguments[1])?);
+    Ok(Object::Number(function(a, b)))
+}
+
+fn floor(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    unary(arguments, f32::floor)
+}
+
+fn ceil(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    unary(arguments, f32::ceil)
+}
+
+/// Rounds halves away from zero.
+fn round(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    unary(arguments, f32::round)
+}
+
+fn abs(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    unary(arguments, f32::abs)
+}
+
+fn sqrt(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    unary(arguments, f32::sqrt)
+}
+
+fn pow(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    binary(arguments, f32::powf)
+}
+
+// Unlike `f32::min` and `f32::max`, these give NaN if either argument is
+// NaN, as any other arithmetic on NaN does.
+
+fn min(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    binary(arguments, |a, b| {
+        if a.is_nan() || b.is_nan() {
+            f32::NAN
+        } else {
+            a.min(b)
+        }
+    })
+}
+
+fn max(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    binary(arguments, |a, b| {
+        if a.is_nan() || b.is_nan() {
+            f32::NAN
+        } else {
+            a.max(b)
+        }
+    })
+}
+
+fn sin(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    unary(arguments, f32::sin)
+}
+
+fn cos(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    unary(arguments, f32::cos)
+}
+
+fn tan(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    unary(arguments, f32::tan)
+}
+
+fn asin(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    unary(arguments, f32::asin)
+}
+
+fn acos(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    unary(arguments, f32::acos)
+}
+
+fn atan(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    unary(arguments, f32::atan)
+}
+
+/// The angle of the point (x, y), given y then x.
+fn atan2(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    binary(arguments, f32::atan2)
+}
+
+/// The natural logarithm.
+fn log(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    unary(arguments, f32::ln)
+}
+
+fn exp(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    unary(arguments, f32::exp)
+}
+
+/// A number from 0 up to but not including 1.
+fn random(interpreter: &Interpreter, _: &[Object]) -> Result<Object, RuntimeError> {
+    interpreter.require(interpreter.capabilities.random, "generate random numbers")?;
+
+    // SplitMix64, which is plenty for scripts and easy to seed.
+    let state = interpreter.random.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+    interpreter.random.set(state);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+
+    // The top 24 bits, which is as many as a number has.
+    Ok(Object::Number((z >> 40) as f32 / (1 << 24) as f32))
+}
+
+/// Restarts `random` from a known point, so the numbers it gives can be
+/// repeated.
+fn seed(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    interpreter
+        .random
+        .set(number(&arguments[0])?.to_bits() as u64);
+    Ok(Object::Nil)
+}
@@ -0,0 +1,164 @@
+//! Methods of strings. Each is passed the string as its first argument.
+//!
+//! Lengths and indexes count characters, meaning Unicode scalar values,
+//! rather than bytes.
+
+use super::{index, new_list, new_string, string};
+use crate::{
+    interpreter::{ErrorKind, Interpreter, RuntimeError},
+    object::{Native, Object},
+};
+
+pub const METHODS: &[Native] = &[
+    Native {
+        name: "len",
+        arity: 0,
+        function: len,
+    },
+    Native {
+        name: "substr",
+        arity: 2,
+        function: substr,
+    },
+    Native {
+        name: "indexOf",
+        arity: 1,
+        function: index_of,
+    },
+    Native {
+        name: "split",
+        arity: 1,
+        function: split,
+    },
+    Native {
+        name: "trim",
+        arity: 0,
+        function: trim,
+    },
+    Native {
+        name: "upper",
+        arity: 0,
+        function: upper,
+    },
+    Native {
+        name: "lower",
+        arity: 0,
+        function: lower,
+    },
+    Native {
+        name: "replace",
+        arity: 2,
+        function: replace,
+    },
+    Native {
+        name: "startsWith",
+        arity: 1,
+        function: starts_with,
+    },
+    Native {
+        name: "endsWith",
+        arity: 1,
+        function: ends_with,
+    },
+    Native {
+        name: "chars",
+        arity: 0,
+        function: chars,
+    },
+];
+
+fn receiver(receiver: &Object) -> &str {
+    match receiver {
+        Object::String(string) => string,
+        _ => unreachable!("string method called on {}", receiver),
+    }
+}
+
+fn len(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    Ok(Object::Number(
+        receiver(&arguments[0]).chars().count() as f32
+    ))
+}
+
+/// The characters from `start` up to but not including `end`.
+fn substr(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let string = receiver(&arguments[0]);
+    let len = string.chars().count();
+    let start = index(&arguments[1], len + 1)?;
+    let end = index(&arguments[2], len + 1)?;
+
+    let substr = string
+        .chars()
+        .skip(start)
+        .take(end.saturating_sub(start))
+        .collect();
+    new_string(interpreter, substr)
+}
+
+/// Where `needle` first appears, or -1 if it doesn't.
+fn index_of(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let needle = string(&arguments[1])?;
+    let string = receiver(&arguments[0]);
+
+    Ok(Object::Number(match string.find(needle) {
+        Some(offset) => string[..offset].chars().count() as f32,
+        None => -1.0,
+    }))
+}
+
+fn split(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let separator = string(&arguments[1])?;
+    let string = receiver(&arguments[0]);
+    if separator.is_empty() {
+        return Err(ErrorKind::ArgumentType {
+            expected: "a non-empty string",
+        }
+        .into());
+    }
+
+    interpreter.allocate(string.len())?;
+    let parts = string
+        .split(separator)
+        .map(|part| Object::String(part.into()))
+        .collect();
+    new_list(interpreter, parts)
+}
+
+fn trim(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    new_string(interpreter, receiver(&arguments[0]).trim().to_owned())
+}
+
+fn upper(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    new_string(interpreter, receiver(&arguments[0]).to_uppercase())
+}
+
+fn lower(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    new_string(interpreter, receiver(&arguments[0]).to_lowercase())
+}
+
+/// Replaces every occurrence of the first argument with the second.
+fn replace(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let from = string(&arguments[1])?;
+    let to = string(&arguments[2])?;
+    new_string(interpreter, receiver(&arguments[0]).replace(from, to))
+}
+
+fn starts_with(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let prefix = string(&arguments[1])?;
+    Ok(Object::Bool(receiver(&arguments[0]).starts_with(prefix)))
+}
+
+fn ends_with(_: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let suffix = string(&arguments[1])?;
+    Ok(Object::Bool(receiver(&arguments[0]).ends_with(suffix)))
+}
+
+fn chars(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RuntimeError> {
+    let string = receiver(&arguments[0]);
+    interpreter.allocate(string.len())?;
+    let chars = string
+        .chars()
+        .map(|char| Object::String(char.to_string().into()))
+        .collect();
+    new_list(interpreter, chars)
+}
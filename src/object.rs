@@ -0,0 +1,338 @@
+use std::{cell::RefCell, fmt, ops::Deref, rc::Rc, sync::Arc};
+
+use indexmap::IndexMap;
+
+use crate::{
+    environment::Environment,
+    interpreter::{Interpreter, RuntimeError, RED_ZONE, STACK_SEGMENT},
+    token::Literal,
+};
+
+/// How deeply nested a collection can be written before the rest is left
+/// out, as `[...]` or `{...}`.
+const MAX_WRITE_DEPTH: usize = 256;
+
+/// A value at runtime.
+#[derive(Debug, Clone)]
+pub enum Object {
+    /// Strings are never changed once made, so copies share them.
+    String(Arc<str>),
+    Number(f32),
+    Bool(bool),
+    Nil,
+    Native(Rc<Native>),
+    /// A native method along with the value it was looked up on.
+    Method(Rc<Method>),
+    List(Rc<Collection<Vec<Object>>>),
+    /// Entries are kept in the order their keys were first inserted.
+    Map(Rc<Collection<IndexMap<Key, Object>>>),
+    Module(Rc<Module>),
+}
+
+impl Object {
+    /// The literal that evaluates to this value, if there is one.
+    pub fn to_literal(&self) -> Option<Literal> {
+        match self {
+            Self::String(string) => Some(Literal::String(string.clone())),
+            Self::Number(number) => Some(Literal::Number(*number)),
+            Self::Bool(bool) => Some(Literal::Bool(*bool)),
+            Self::Nil => Some(Literal::Nil),
+            Self::Native(_) | Self::Method(_) | Self::List(_) | Self::Map(_) | Self::Module(_) => {
+                None
+            }
+        }
+    }
+
+    pub fn list(elements: Vec<Object>) -> Self {
+        Self::List(Rc::new(Collection(RefCell::new(elements))))
+    }
+
+    pub fn map(entries: IndexMap<Key, Object>) -> Self {
+        Self::Map(Rc::new(Collection(RefCell::new(entries))))
+    }
+
+    /// Writes the value, showing strings in quotes if they're inside a
+    /// collection. `outer` holds the collections being written around this
+    /// one, so a list or map that contains itself, or is nested too deeply,
+    /// comes out as `[...]` or `{...}` rather than going on forever.
+    fn write(&self, f: &mut fmt::Formatter<'_>, outer: &mut Vec<*const ()>) -> fmt::Result {
+        stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || self.write_value(f, outer))
+    }
+
+    fn write_value(&self, f: &mut fmt::Formatter<'_>, outer: &mut Vec<*const ()>) -> fmt::Result {
+        match self {
+            Self::String(string) if !outer.is_empty() => write!(f, "\"{}\"", string),
+            Self::List(list) => {
+                let address = Rc::as_ptr(list) as *const ();
+                if outer.contains(&address) || outer.len() >= MAX_WRITE_DEPTH {
+                    return write!(f, "[...]");
+                }
+
+                outer.push(address);
+                write!(f, "[")?;
+                for (i, element) in list.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.write(f, outer)?;
+                }
+                outer.pop();
+                write!(f, "]")
+            }
+            Self::Map(map) => {
+                let address = Rc::as_ptr(map) as *const ();
+                if outer.contains(&address) || outer.len() >= MAX_WRITE_DEPTH {
+                    return write!(f, "{{...}}");
+                }
+
+                outer.push(address);
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: ", key)?;
+                    value.write(f, outer)?;
+                }
+                outer.pop();
+                write!(f, "}}")
+            }
+            Self::String(string) => write!(f, "{}", string),
+            Self::Number(number) => write!(f, "{}", number),
+            Self::Bool(bool) => write!(f, "{}", bool),
+            Self::Nil => write!(f, "nil"),
+            Self::Native(native) => write!(f, "<native fn {}>", native.name),
+            Self::Method(method) => write!(f, "<native fn {}>", method.native.name),
+            Self::Module(module) => write!(f, "<module {}>", module.path),
+        }
+    }
+}
+
+impl From<Literal> for Object {
+    fn from(literal: Literal) -> Self {
+        match literal {
+            Literal::String(string) => Self::String(string),
+            Literal::Number(number) => Self::Number(number),
+            Literal::Bool(bool) => Self::Bool(bool),
+            Literal::Nil => Self::Nil,
+        }
+    }
+}
+
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::Number(a), Self::Number(b)) => a == b,
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Nil, Self::Nil) => true,
+            (Self::Native(a), Self::Native(b)) => Rc::ptr_eq(a, b),
+            (Self::Method(a), Self::Method(b)) => Rc::ptr_eq(a, b),
+            (Self::List(a), Self::List(b)) => Rc::ptr_eq(a, b),
+            (Self::Map(a), Self::Map(b)) => Rc::ptr_eq(a, b),
+            (Self::Module(a), Self::Module(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, &mut Vec::new())
+    }
+}
+
+/// What a list or map holds, shared between everything that refers to it.
+///
+/// Dropping a collection drops any nested in it one at a time rather than
+/// recursively, since a program can nest them far deeper than the native
+/// stack would allow.
+#[derive(Debug)]
+pub struct Collection<T: Contents>(RefCell<T>);
+
+impl<T: Contents> Deref for Collection<T> {
+    type Target = RefCell<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: Contents> Drop for Collection<T> {
+    fn drop(&mut self) {
+        let mut values = Vec::new();
+        self.0.get_mut().take_values(&mut values);
+
+        // Only values nothing else refers to are taken apart; the rest just
+        // lose a reference.
+        while let Some(value) = values.pop() {
+            match value {
+                Object::List(list) => {
+                    if let Ok(mut list) = Rc::try_unwrap(list) {
+                        list.0.get_mut().take_values(&mut values);
+                    }
+                }
+                Object::Map(map) => {
+                    if let Ok(mut map) = Rc::try_unwrap(map) {
+                        map.0.get_mut().take_values(&mut values);
+                    }
+                }
+                Object::Method(method) => {
+                    if let Ok(method) = Rc::try_unwrap(method) {
+                        values.push(method.receiver);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+pub trait Contents {
+    /// Moves the values held onto the end of `values`.
+    fn take_values(&mut self, values: &mut Vec<Object>);
+}
+
+impl Contents for Vec<Object> {
+    fn take_values(&mut self, values: &mut Vec<Object>) {
+        values.append(self);
+    }
+}
+
+impl Contents for IndexMap<Key, Object> {
+    fn take_values(&mut self, values: &mut Vec<Object>) {
+        values.extend(self.drain(..).map(|(_, value)| value));
+    }
+}
+
+/// A function built into the interpreter. Methods are passed the value they
+/// were looked up on as their first argument, which `arity` doesn't count.
+#[derive(Debug, Clone)]
+pub struct Native {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: fn(&Interpreter, &[Object]) -> Result<Object, RuntimeError>,
+}
+
+/// A value that can be used as a key in a map.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    String(Arc<str>),
+    /// The bits of the number, with `-0` stored as `0` so the two find the
+    /// same entry.
+    Number(u32),
+    Bool(bool),
+    Nil,
+}
+
+impl Key {
+    /// The key for `value`, if it can be used as one.
+    pub fn new(value: &Object) -> Option<Self> {
+        match value {
+            Object::String(string) => Some(Self::String(string.clone())),
+            Object::Number(number) => Some(Self::Number((number + 0.0).to_bits())),
+            Object::Bool(bool) => Some(Self::Bool(*bool)),
+            Object::Nil => Some(Self::Nil),
+            _ => None,
+        }
+    }
+
+    pub fn to_object(&self) -> Object {
+        match self {
+            Self::String(string) => Object::String(string.clone()),
+            Self::Number(bits) => Object::Number(f32::from_bits(*bits)),
+            Self::Bool(bool) => Object::Bool(*bool),
+            Self::Nil => Object::Nil,
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(string) => write!(f, "\"{}\"", string),
+            key => write!(f, "{}", key.to_object()),
+        }
+    }
+}
+
+/// A file brought in with `import`, whose top-level names can be got at
+/// with `.`.
+#[derive(Debug)]
+pub struct Module {
+    /// The path it was imported by.
+    pub path: String,
+    pub globals: Rc<RefCell<Environment>>,
+}
+
+#[derive(Debug)]
+pub struct Method {
+    pub receiver: Object,
+    pub native: &'static Native,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let inner = Object::list(vec![Object::String("a".into()), Object::Nil]);
+        let outer = Object::list(vec![Object::Number(1.5), inner.clone(), inner]);
+        assert_eq!(outer.to_string(), "[1.5, [\"a\", nil], [\"a\", nil]]");
+
+        if let Object::List(list) = &outer {
+            list.borrow_mut().push(outer.clone());
+        }
+        assert_eq!(
+            outer.to_string(),
+            "[1.5, [\"a\", nil], [\"a\", nil], [...]]"
+        );
+
+        let map = Object::map(IndexMap::from([
+            (Key::String("b".into()), Object::Number(1.0)),
+            (Key::Nil, Object::list(Vec::new())),
+            (Key::Number(2.0f32.to_bits()), Object::Bool(true)),
+        ]));
+        assert_eq!(map.to_string(), "{\"b\": 1, nil: [], 2: true}");
+        if let Object::Map(entries) = &map {
+            entries.borrow_mut().insert(Key::Bool(false), map.clone());
+        }
+        assert_eq!(
+            map.to_string(),
+            "{\"b\": 1, nil: [], 2: true, false: {...}}"
+        );
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let mut value = Object::Nil;
+        for i in 0..1_000_000 {
+            value = if i % 2 == 0 {
+                Object::list(vec![value])
+            } else {
+                Object::map(IndexMap::from([(Key::Nil, value)]))
+            };
+        }
+
+        let written = value.to_string();
+        assert!(written.starts_with("{nil: [{nil: ["));
+        assert_eq!(written.matches("nil: ").count(), MAX_WRITE_DEPTH / 2);
+        assert!(written.contains("[{...}]"));
+        // Dropping it mustn't overflow the stack either.
+        drop(value);
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(
+            Key::new(&Object::Number(-0.0)),
+            Key::new(&Object::Number(0.0))
+        );
+        assert_eq!(
+            Key::new(&Object::String("a".into())).map(|key| key.to_object()),
+            Some(Object::String("a".into()))
+        );
+        assert_eq!(Key::new(&Object::list(Vec::new())), None);
+    }
+}
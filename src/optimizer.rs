@@ -0,0 +1,350 @@
+use crate::{
+    expr::Expr,
+    interpreter::{Interpreter, RED_ZONE, STACK_SEGMENT},
+    object::Object,
+    stmt::Stmt,
+};
+
+/// Folds constant subexpressions ahead of evaluation.
+///
+/// A node is folded by evaluating it with the interpreter once all of its
+/// operands have been reduced to literals, so a folded value is always the
+/// value evaluation would have produced. Nodes that fail to evaluate (e.g.
+/// `-"a"`) are left in place so the error is still raised at runtime.
+pub struct Optimizer {
+    interpreter: Interpreter,
+}
+
+impl Optimizer {
+    pub fn new() -> Self {
+        Self {
+            interpreter: Interpreter::new(),
+        }
+    }
+
+    pub fn optimize(&self, statements: &[Stmt]) -> Vec<Stmt> {
+        statements
+            .iter()
+            .filter_map(|statement| self.statement(statement))
+            .collect()
+    }
+
+    /// The optimized statement, or `None` if it could never do anything.
+    fn statement(&self, stmt: &Stmt) -> Option<Stmt> {
+        let stmt = match stmt {
+            Stmt::Expression { expression } => Stmt::Expression {
+                expression: self.expression(expression),
+            },
+            Stmt::Print { expression } => Stmt::Print {
+                expression: self.expression(expression),
+            },
+            Stmt::Var { name, initializer } => Stmt::Var {
+                name: name.clone(),
+                initializer: initializer
+                    .as_ref()
+                    .map(|initializer| self.expression(initializer)),
+            },
+            Stmt::Block { statements } => Stmt::Block {
+                statements: self.optimize(statements),
+            },
+            Stmt::Import { .. } => stmt.clone(),
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => Stmt::ForIn {
+                name: name.clone(),
+                iterable: self.expression(iterable),
+                body: self.body(body),
+            },
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = self.expression(condition);
+                match self.constant_truth(&condition) {
+                    Some(true) => return self.statement(then_branch),
+                    Some(false) => {
+                        return else_branch
+                            .as_ref()
+                            .and_then(|branch| self.statement(branch));
+                    }
+                    None => Stmt::If {
+                        condition,
+                        then_branch: self.body(then_branch),
+                        else_branch: else_branch
+                            .as_ref()
+                            .and_then(|branch| self.statement(branch))
+                            .map(Box::new),
+                    },
+                }
+            }
+            Stmt::While { condition, body } => {
+                let condition = self.expression(condition);
+                if self.constant_truth(&condition) == Some(false) {
+                    return None;
+                }
+                Stmt::While {
+                    condition,
+                    body: self.body(body),
+                }
+            }
+        };
+        Some(stmt)
+    }
+
+    /// A statement in a position that needs one, where one that was
+    /// optimized away is replaced by an empty block.
+    fn body(&self, stmt: &Stmt) -> Box<Stmt> {
+        Box::new(self.statement(stmt).unwrap_or(Stmt::Block {
+            statements: Vec::new(),
+        }))
+    }
+
+    /// Whether `condition` is always truthy or always falsey, if it is a
+    /// literal.
+    fn constant_truth(&self, condition: &Expr) -> Option<bool> {
+        match condition {
+            Expr::Literal { literal } => {
+                Some(self.interpreter.is_truthy(Object::from(literal.clone())))
+            }
+            _ => None,
+        }
+    }
+
+    fn expression(&self, expr: &Expr) -> Expr {
+        // Trees from the parser are shallow, but ones built by hand needn't
+        // be.
+        stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || self.reduce(expr))
+    }
+
+    fn reduce(&self, expr: &Expr) -> Expr {
+        match expr {
+            Expr::Literal { .. } | Expr::Variable { .. } => expr.clone(),
+            Expr::Assign { name, value } => Expr::Assign {
+                name: name.clone(),
+                value: Box::new(self.expression(value)),
+            },
+            Expr::Grouping { expression } => match self.expression(expression) {
+                literal @ Expr::Literal { .. } => literal,
+                expression => Expr::Grouping {
+                    expression: Box::new(expression),
+                },
+            },
+            Expr::Unary { operator, right } => self.fold(Expr::Unary {
+                operator: operator.clone(),
+                right: Box::new(self.expression(right)),
+            }),
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => self.fold(Expr::Binary {
+                left: Box::new(self.expression(left)),
+                operator: operator.clone(),
+                right: Box::new(self.expression(right)),
+            }),
+            // Calls can have side effects, and every evaluation of a list
+            // literal makes a new list, so for the rest only their operands
+            // are folded.
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => Expr::Call {
+                callee: Box::new(self.expression(callee)),
+                paren: paren.clone(),
+                arguments: arguments
+                    .iter()
+                    .map(|argument| self.expression(argument))
+                    .collect(),
+            },
+            Expr::Get { object, name } => Expr::Get {
+                object: Box::new(self.expression(object)),
+                name: name.clone(),
+            },
+            Expr::List { elements } => Expr::List {
+                elements: elements
+                    .iter()
+                    .map(|element| self.expression(element))
+                    .collect(),
+            },
+            Expr::Map { brace, entries } => Expr::Map {
+                brace: brace.clone(),
+                entries: entries
+                    .iter()
+                    .map(|(key, value)| (self.expression(key), self.expression(value)))
+                    .collect(),
+            },
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => Expr::Index {
+                object: Box::new(self.expression(object)),
+                bracket: bracket.clone(),
+                index: Box::new(self.expression(index)),
+            },
+            Expr::SetIndex {
+                object,
+                bracket,
+                index,
+                value,
+            } => Expr::SetIndex {
+                object: Box::new(self.expression(object)),
+                bracket: bracket.clone(),
+                index: Box::new(self.expression(index)),
+                value: Box::new(self.expression(value)),
+            },
+        }
+    }
+
+    fn fold(&self, expr: Expr) -> Expr {
+        if !self.is_constant(&expr) {
+            return expr;
+        }
+
+        match self
+            .interpreter
+            .evaluate(&expr)
+            .map(|value| value.to_literal())
+        {
+            Ok(Some(literal)) => Expr::Literal { literal },
+            _ => expr,
+        }
+    }
+
+    fn is_constant(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Literal { .. } => true,
+            Expr::Variable { .. }
+            | Expr::Assign { .. }
+            | Expr::Call { .. }
+            | Expr::Get { .. }
+            | Expr::List { .. }
+            | Expr::Map { .. }
+            | Expr::Index { .. }
+            | Expr::SetIndex { .. } => false,
+            Expr::Grouping { expression } => self.is_constant(expression),
+            Expr::Unary { right, .. } => matches!(**right, Expr::Literal { .. }),
+            Expr::Binary { left, right, .. } => {
+                matches!(**left, Expr::Literal { .. }) && matches!(**right, Expr::Literal { .. })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{buffer::Buffer, generator, lexer::Lexer, parser::Parser, printer::Printer};
+
+    use proptest::prelude::*;
+
+    fn optimize(source: &str) -> String {
+        let tokens = Lexer::new(source).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        Optimizer::new()
+            .optimize(&statements)
+            .iter()
+            .map(|statement| statement.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(optimize("(1 + 2) * 3;"), "(; 9)");
+        assert_eq!(optimize("10 - 4 / 2;"), "(; 8)");
+    }
+
+    #[test]
+    fn comparison() {
+        assert_eq!(optimize("1 + 1 == 2;"), "(; true)");
+        assert_eq!(optimize("3 < 2;"), "(; false)");
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(optimize("\"foo\" + \"bar\";"), "(; foobar)");
+    }
+
+    #[test]
+    fn bang() {
+        assert_eq!(optimize("!nil;"), "(; true)");
+        assert_eq!(optimize("!!(1 < 2);"), "(; true)");
+    }
+
+    #[test]
+    fn statements() {
+        assert_eq!(
+            optimize("var a = 1 + 2; { print a * (2 * 3); a = -a; }"),
+            "(var a 3) (block (print (* a 6)) (; (= a (- a))))"
+        );
+    }
+
+    #[test]
+    fn dead_branches() {
+        assert_eq!(optimize("if (false) print 1; print 2;"), "(print 2)");
+        assert_eq!(
+            optimize("if (1 < 2) print \"a\"; else print \"b\";"),
+            "(print a)"
+        );
+        assert_eq!(
+            optimize("if (nil) print 1; else { print 2; }"),
+            "(block (print 2))"
+        );
+        assert_eq!(optimize("while (!true) print 1;"), "");
+        assert_eq!(
+            optimize("if (a) if (false) print 1; else print 2 + 3;"),
+            "(if a (print 5))"
+        );
+        assert_eq!(
+            optimize("if (a) { if (false) print 1; } else print 2;"),
+            "(if a (block) (print 2))"
+        );
+        assert_eq!(
+            optimize("for (var x in xs) if (false) print x;"),
+            "(for-in x xs (block))"
+        );
+        assert_eq!(optimize("while (a) print 1 + 1;"), "(while a (print 2))");
+    }
+
+    #[test]
+    fn calls() {
+        assert_eq!(optimize("clock(1 + 2);"), "(; (call clock 3))");
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(optimize("[1 + 2, [3]][0];"), "(; ([] (list 3 (list 3)) 0))");
+        assert_eq!(optimize("a[1 - 1] = (1).len;"), "(; ([]= a 0 (. 1 len)))");
+    }
+
+    #[test]
+    fn preserves_runtime_errors() {
+        assert_eq!(optimize("-\"a\";"), "(; (- a))");
+        assert_eq!(optimize("(1 + 2) * -\"a\";"), "(; (* 3 (- a)))");
+        assert_eq!(optimize("\"a\" + 1;"), "(; (+ a 1))");
+    }
+
+    proptest! {
+        /// Programs behave the same with and without optimization.
+        #[test]
+        fn differential(program in generator::program()) {
+            // Go through the source, so the front end is exercised too.
+            let source = Printer::new().print(&program);
+            let statements = Parser::new(Lexer::new(&source).scan_tokens()).parse().unwrap();
+
+            let run = |statements: &[Stmt]| {
+                let output = Buffer::new();
+                let mut interpreter = Interpreter::new().output(output.clone());
+                let result = interpreter.interpret(statements).map_err(|error| error.trace());
+                (result, output.contents(), format!("{:?}", interpreter.globals()))
+            };
+
+            prop_assert_eq!(run(&statements), run(&Optimizer::new().optimize(&statements)));
+        }
+    }
+}
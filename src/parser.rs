@@ -1,33 +1,286 @@
 use crate::expr::Expr;
+use crate::interpreter::{RED_ZONE, STACK_SEGMENT};
+use crate::stmt::Stmt;
 use crate::token::{Literal, Token};
 use crate::token_type::TokenType;
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use thiserror::Error;
 
+/// The most arguments a call can have.
+const MAX_ARGUMENTS: usize = 255;
+/// How deeply expressions and statements may nest. Everything that walks
+/// the syntax tree recurses, so this keeps trees shallow enough for all of
+/// them to stay within the native stack.
+pub(crate) const MAX_NESTING: usize = 256;
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    /// How deeply nested the tree being built is at this point.
+    depth: usize,
+    repl: bool,
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+    pub fn new(mut tokens: Vec<Token>) -> Self {
+        // Everything relies on there being an Eof token to stop at.
+        if tokens.last().map(|token| &token.token_type) != Some(&TokenType::Eof) {
+            let line = tokens.last().map_or(1, |token| token.get_line());
+            tokens.push(Token::new(TokenType::Eof, "".to_owned(), None, line));
+        }
+
+        Self {
+            tokens,
+            current: 0,
+            depth: 0,
+            repl: false,
+        }
     }
 
-    pub fn parse(&mut self) -> Result<Box<Expr>> {
-        self.expression()
+    /// Allow the final statement to be an expression without a trailing
+    /// semicolon, as is convenient when typing into the REPL.
+    pub fn repl(mut self) -> Self {
+        self.repl = true;
+        self
+    }
+
+    pub fn parse(&mut self) -> Result<Vec<Stmt>> {
+        // Report anything the lexer couldn't make sense of first.
+        if let Some(token) = self
+            .tokens
+            .iter()
+            .find(|token| token.token_type == TokenType::Error)
+        {
+            let message = if token.get_lexeme().starts_with('"') {
+                "Unterminated string."
+            } else {
+                "Unexpected character."
+            };
+            return Err(ParseError::new(token, message).into());
+        }
+
+        let mut statements = Vec::new();
+
+        while !self.is_at_end() {
+            statements.push(self.declaration()?);
+        }
+
+        Ok(statements)
+    }
+
+    fn declaration(&mut self) -> Result<Stmt> {
+        if self.matches(vec![TokenType::Var].into_iter()) {
+            return self.var_declaration();
+        }
+
+        if self.matches(vec![TokenType::Import].into_iter()) {
+            return self.import_declaration();
+        }
+
+        self.statement()
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt> {
+        let name = self
+            .consume(TokenType::Identifier, "Expect variable name.")?
+            .clone();
+
+        let initializer = if self.matches(vec![TokenType::Equal].into_iter()) {
+            Some(*self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+
+        Ok(Stmt::Var { name, initializer })
+    }
+
+    fn import_declaration(&mut self) -> Result<Stmt> {
+        let path = self
+            .consume(TokenType::String, "Expect module path after 'import'.")?
+            .clone();
+        self.consume(TokenType::As, "Expect 'as' after module path.")?;
+        let name = self
+            .consume(TokenType::Identifier, "Expect module name after 'as'.")?
+            .clone();
+        self.consume(TokenType::Semicolon, "Expect ';' after import.")?;
+
+        Ok(Stmt::Import { path, name })
+    }
+
+    fn statement(&mut self) -> Result<Stmt> {
+        if self.matches(vec![TokenType::For].into_iter()) {
+            return self.for_statement();
+        }
+
+        if self.matches(vec![TokenType::If].into_iter()) {
+            return self.if_statement();
+        }
+
+        if self.matches(vec![TokenType::While].into_iter()) {
+            return self.while_statement();
+        }
+
+        if self.matches(vec![TokenType::Print].into_iter()) {
+            return self.print_statement();
+        }
+
+        if !self.starts_map() && self.matches(vec![TokenType::LeftBrace].into_iter()) {
+            return Ok(Stmt::Block {
+                statements: self.block()?,
+            });
+        }
+
+        self.expression_statement()
+    }
+
+    /// Whether the `{` starting a statement opens a map rather than a
+    /// block, which is when it's followed by a simple key and a colon.
+    fn starts_map(&self) -> bool {
+        let token_type = |offset| {
+            self.tokens
+                .get(self.current + offset)
+                .map(|token: &Token| &token.token_type)
+        };
+
+        token_type(0) == Some(&TokenType::LeftBrace)
+            && matches!(
+                token_type(1),
+                Some(
+                    TokenType::String
+                        | TokenType::Number
+                        | TokenType::Identifier
+                        | TokenType::True
+                        | TokenType::False
+                        | TokenType::Nil
+                )
+            )
+            && token_type(2) == Some(&TokenType::Colon)
+    }
+
+    fn for_statement(&mut self) -> Result<Stmt> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+        self.consume(TokenType::Var, "Expect 'var' after '('.")?;
+        let name = self
+            .consume(TokenType::Identifier, "Expect variable name.")?
+            .clone();
+        self.consume(TokenType::In, "Expect 'in' after loop variable.")?;
+        let iterable = *self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+        let body = Box::new(self.nested(Self::statement)?);
+
+        Ok(Stmt::ForIn {
+            name,
+            iterable,
+            body,
+        })
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = *self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
+
+        // An `else` goes with the nearest `if` it can.
+        let then_branch = Box::new(self.nested(Self::statement)?);
+        let else_branch = if self.matches(vec![TokenType::Else].into_iter()) {
+            Some(Box::new(self.nested(Self::statement)?))
+        } else {
+            None
+        };
+
+        Ok(Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = *self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        let body = Box::new(self.nested(Self::statement)?);
+
+        Ok(Stmt::While { condition, body })
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt> {
+        let expression = *self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+
+        Ok(Stmt::Print { expression })
+    }
+
+    fn expression_statement(&mut self) -> Result<Stmt> {
+        let expression = *self.expression()?;
+
+        if !(self.repl && self.is_at_end()) {
+            self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        }
+
+        Ok(Stmt::Expression { expression })
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>> {
+        let mut statements = Vec::new();
+
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            statements.push(self.nested(Self::declaration)?);
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+
+        Ok(statements)
     }
 
     fn expression(&mut self) -> Result<Box<Expr>> {
-        self.equality()
+        self.nested(Self::assignment)
+    }
+
+    fn assignment(&mut self) -> Result<Box<Expr>> {
+        let expr = self.equality()?;
+
+        if self.matches(vec![TokenType::Equal].into_iter()) {
+            if !matches!(*expr, Expr::Variable { .. } | Expr::Index { .. }) {
+                return Err(ParseError::new(self.previous(), "Invalid assignment target.").into());
+            }
+            let value = self.nested(Self::assignment)?;
+
+            return Ok(Box::new(match &*expr {
+                Expr::Variable { name } => Expr::Assign {
+                    name: name.clone(),
+                    value,
+                },
+                Expr::Index {
+                    object,
+                    bracket,
+                    index,
+                } => Expr::SetIndex {
+                    object: object.clone(),
+                    bracket: bracket.clone(),
+                    index: index.clone(),
+                    value,
+                },
+                _ => unreachable!(),
+            }));
+        }
+
+        Ok(expr)
     }
 
     fn equality(&mut self) -> Result<Box<Expr>> {
+        let depth = self.depth;
         let mut expr = self.comparison()?;
 
         while self.matches(vec![TokenType::BangEqual, TokenType::EqualEqual].into_iter()) {
             let operator = self.previous().clone();
+            self.deepen()?;
             let right = self.comparison()?.clone();
             expr = Box::new(Expr::Binary {
                 left: expr,
@@ -35,11 +288,13 @@ impl Parser {
                 right,
             })
         }
+        self.depth = depth;
 
         Ok(expr)
     }
 
     fn comparison(&mut self) -> Result<Box<Expr>> {
+        let depth = self.depth;
         let mut expr = self.term()?;
 
         while self.matches(
@@ -52,6 +307,7 @@ impl Parser {
             .into_iter(),
         ) {
             let operator = self.previous().clone();
+            self.deepen()?;
             let right = self.term()?.clone();
             expr = Box::new(Expr::Binary {
                 left: expr,
@@ -59,15 +315,18 @@ impl Parser {
                 right,
             })
         }
+        self.depth = depth;
 
         Ok(expr)
     }
 
     fn term(&mut self) -> Result<Box<Expr>> {
+        let depth = self.depth;
         let mut expr = self.factor()?;
 
         while self.matches(vec![TokenType::Minus, TokenType::Plus].into_iter()) {
             let operator = self.previous().clone();
+            self.deepen()?;
             let right = self.factor()?.clone();
             expr = Box::new(Expr::Binary {
                 left: expr,
@@ -75,15 +334,19 @@ impl Parser {
                 right,
             })
         }
+        self.depth = depth;
 
         Ok(expr)
     }
 
     fn factor(&mut self) -> Result<Box<Expr>> {
+        let depth = self.depth;
         let mut expr = self.unary()?;
 
-        while self.matches(vec![TokenType::Slash, TokenType::Star].into_iter()) {
+        while self.matches(vec![TokenType::Slash, TokenType::Star, TokenType::Percent].into_iter())
+        {
             let operator = self.previous().clone();
+            self.deepen()?;
             let right = self.unary()?.clone();
             expr = Box::new(Expr::Binary {
                 left: expr,
@@ -91,6 +354,7 @@ impl Parser {
                 right,
             });
         }
+        self.depth = depth;
 
         Ok(expr)
     }
@@ -98,13 +362,78 @@ impl Parser {
     fn unary(&mut self) -> Result<Box<Expr>> {
         let match_vec = vec![TokenType::Bang, TokenType::Minus].into_iter();
 
-        while self.matches(match_vec) {
+        if self.matches(match_vec) {
             let operator = self.previous().clone();
-            let right = self.unary()?.clone();
+            let right = self.nested(Self::unary)?;
             return Ok(Box::new(Expr::Unary { operator, right }));
         }
 
-        self.primary()
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<Box<Expr>> {
+        let depth = self.depth;
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.matches(vec![TokenType::LeftParen].into_iter()) {
+                self.deepen()?;
+                expr = self.finish_call(expr)?;
+            } else if self.matches(vec![TokenType::Dot].into_iter()) {
+                self.deepen()?;
+                let name = self
+                    .consume(TokenType::Identifier, "Expect property name after '.'.")?
+                    .clone();
+                expr = Box::new(Expr::Get { object: expr, name });
+            } else if self.matches(vec![TokenType::LeftBracket].into_iter()) {
+                self.deepen()?;
+                let index = self.expression()?;
+                let bracket = self
+                    .consume(TokenType::RightBracket, "Expect ']' after index.")?
+                    .clone();
+                expr = Box::new(Expr::Index {
+                    object: expr,
+                    bracket,
+                    index,
+                });
+            } else {
+                break;
+            }
+        }
+        self.depth = depth;
+
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Box<Expr>) -> Result<Box<Expr>> {
+        let mut arguments = Vec::new();
+
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    return Err(ParseError::new(
+                        self.peek(),
+                        "Can't have more than 255 arguments.",
+                    )
+                    .into());
+                }
+                arguments.push(*self.expression()?);
+
+                if !self.matches(vec![TokenType::Comma].into_iter()) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self
+            .consume(TokenType::RightParen, "Expect ')' after arguments.")?
+            .clone();
+
+        Ok(Box::new(Expr::Call {
+            callee,
+            paren,
+            arguments,
+        }))
     }
 
     fn primary(&mut self) -> Result<Box<Expr>> {
@@ -132,17 +461,81 @@ impl Parser {
             }));
         }
 
+        if self.matches(vec![TokenType::Identifier].into_iter()) {
+            return Ok(Box::new(Expr::Variable {
+                name: self.previous().clone(),
+            }));
+        }
+
+        if self.matches(vec![TokenType::LeftBracket].into_iter()) {
+            let mut elements = Vec::new();
+            if !self.check(TokenType::RightBracket) {
+                loop {
+                    elements.push(*self.expression()?);
+                    if !self.matches(vec![TokenType::Comma].into_iter()) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+
+            return Ok(Box::new(Expr::List { elements }));
+        }
+
+        if self.matches(vec![TokenType::LeftBrace].into_iter()) {
+            let mut entries = Vec::new();
+            if !self.check(TokenType::RightBrace) {
+                loop {
+                    let key = *self.expression()?;
+                    self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+                    let value = *self.expression()?;
+                    entries.push((key, value));
+
+                    if !self.matches(vec![TokenType::Comma].into_iter()) {
+                        break;
+                    }
+                }
+            }
+            let brace = self
+                .consume(TokenType::RightBrace, "Expect '}' after map entries.")?
+                .clone();
+
+            return Ok(Box::new(Expr::Map { brace, entries }));
+        }
+
         if self.matches(vec![TokenType::LeftParen].into_iter()) {
             let expr = self.expression()?;
-            self.consume(TokenType::RightParen)
-                .context("Expect ')' after expression.")?;
+            self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
 
             return Ok(Box::new(Expr::Grouping { expression: expr }));
         }
 
-        Err(ParseError::ExpectExpression.into())
+        Err(ParseError::new(self.peek(), "Expect expression.").into())
+    }
+
+    /// Runs `parse` one level deeper in the tree.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.deepen()?;
+        // Each level takes several frames, which can add up to more than a
+        // small thread's stack well before `MAX_NESTING`.
+        let result = stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || parse(self));
+        self.depth -= 1;
+
+        result
+    }
+
+    /// Goes one level deeper in the tree, failing rather than going past
+    /// `MAX_NESTING`.
+    fn deepen(&mut self) -> Result<()> {
+        if self.depth >= MAX_NESTING {
+            return Err(ParseError::new(self.peek(), "Too much nesting.").into());
+        }
+        self.depth += 1;
+
+        Ok(())
     }
 
+    #[allow(dead_code)]
     fn synchronise(&mut self) {
         self.advance();
 
@@ -155,6 +548,7 @@ impl Parser {
                 TokenType::Class
                 | TokenType::Fun
                 | TokenType::Var
+                | TokenType::Import
                 | TokenType::For
                 | TokenType::If
                 | TokenType::While
@@ -169,12 +563,12 @@ impl Parser {
         self.advance();
     }
 
-    fn consume(&mut self, token_type: TokenType) -> Result<&Token> {
+    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<&Token> {
         if self.check(token_type) {
             return Ok(self.advance());
         }
 
-        Err(ParseError::Consume.into())
+        Err(ParseError::new(self.peek(), message).into())
     }
 
     fn matches(&mut self, token_types: impl Iterator<Item = TokenType>) -> bool {
@@ -203,23 +597,312 @@ impl Parser {
         self.previous()
     }
 
-    fn is_at_end(&self) -> bool {
+    /// Whether the parser has run out of tokens. After a failed parse this
+    /// means the input stopped part way through, so more of it might still
+    /// make a valid program.
+    pub fn is_at_end(&self) -> bool {
         self.peek().token_type == TokenType::Eof
     }
 
     fn peek(&self) -> &Token {
-        self.tokens.get(self.current).unwrap()
+        &self.tokens[self.current]
     }
 
     fn previous(&self) -> &Token {
-        self.tokens.get(self.current - 1).unwrap()
+        &self.tokens[self.current.saturating_sub(1)]
     }
 }
 
+/// A syntax error, worded the way the reference implementation words them.
 #[derive(Error, Debug)]
-enum ParseError {
-    #[error("failed to consume")]
-    Consume,
-    #[error("expected expression")]
-    ExpectExpression,
+#[error("[line {line}] Error{location}: {message}")]
+pub struct ParseError {
+    line: usize,
+    location: String,
+    message: String,
+}
+
+impl ParseError {
+    fn new(token: &Token, message: &str) -> Self {
+        let location = match token.token_type {
+            TokenType::Eof => " at end".to_owned(),
+            TokenType::Error => "".to_owned(),
+            _ => format!(" at '{}'", token.get_lexeme()),
+        };
+
+        Self {
+            line: token.get_line(),
+            location,
+            message: message.to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn parse(source: &str, repl: bool) -> (Result<Vec<Stmt>>, bool) {
+        let tokens = Lexer::new(source).scan_tokens();
+        let mut parser = Parser::new(tokens);
+        if repl {
+            parser = parser.repl();
+        }
+        (parser.parse(), parser.is_at_end())
+    }
+
+    #[test]
+    fn statements() {
+        let statements = parse("var a = 1; { print a; a = 2; }", false).0.unwrap();
+        let printed: Vec<_> = statements.iter().map(|s| s.to_string()).collect();
+
+        assert_eq!(printed, vec!["(var a 1)", "(block (print a) (; (= a 2)))"]);
+    }
+
+    #[test]
+    fn lists() {
+        let statements = parse("xs[0] = [1, []].len;", false).0.unwrap();
+        assert_eq!(
+            statements[0].to_string(),
+            "(; ([]= xs 0 (. (list 1 (list)) len)))"
+        );
+
+        let error = |source| parse(source, false).0.unwrap_err().to_string();
+        assert_eq!(
+            error("xs.len = 1;"),
+            "[line 1] Error at '=': Invalid assignment target."
+        );
+        assert_eq!(
+            error("xs.1;"),
+            "[line 1] Error at '1': Expect property name after '.'."
+        );
+        assert_eq!(
+            error("xs[0;"),
+            "[line 1] Error at ';': Expect ']' after index."
+        );
+    }
+
+    #[test]
+    fn maps() {
+        let printed = |source| {
+            parse(source, false)
+                .0
+                .unwrap()
+                .iter()
+                .map(|statement| statement.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            printed("var m = {\"a\": 1, 2: {}};"),
+            vec!["(var m (map (a 1) (2 (map))))"]
+        );
+        // At the start of a statement, braces are a block unless they're
+        // followed by a key and a colon.
+        assert_eq!(printed("{a: 1}[a];"), vec!["(; ([] (map (a 1)) a))"]);
+        assert_eq!(printed("{a;}"), vec!["(block (; a))"]);
+        assert_eq!(printed("{}"), vec!["(block)"]);
+        assert_eq!(printed("({});"), vec!["(; (group (map)))"]);
+
+        let error = |source| parse(source, false).0.unwrap_err().to_string();
+        assert_eq!(
+            error("print {1 2};"),
+            "[line 1] Error at '2': Expect ':' after map key."
+        );
+        assert_eq!(
+            error("print {1: 2;"),
+            "[line 1] Error at ';': Expect '}' after map entries."
+        );
+    }
+
+    #[test]
+    fn for_in() {
+        let statements = parse("for (var x in [1]) { print x; }", false).0.unwrap();
+        assert_eq!(
+            statements[0].to_string(),
+            "(for-in x (list 1) (block (print x)))"
+        );
+
+        let error = |source| parse(source, false).0.unwrap_err().to_string();
+        assert_eq!(
+            error("for (x in xs) print x;"),
+            "[line 1] Error at 'x': Expect 'var' after '('."
+        );
+        assert_eq!(
+            error("for (var x = xs) print x;"),
+            "[line 1] Error at '=': Expect 'in' after loop variable."
+        );
+        assert_eq!(
+            error("for (var x in xs) var y;"),
+            "[line 1] Error at 'var': Expect expression."
+        );
+    }
+
+    #[test]
+    fn if_while() {
+        let statements = parse("if (a) if (b) print 1; else print 2; while (c) {}", false)
+            .0
+            .unwrap();
+        assert_eq!(
+            statements[0].to_string(),
+            "(if a (if b (print 1) (print 2)))"
+        );
+        assert_eq!(statements[1].to_string(), "(while c (block))");
+
+        let error = |source| parse(source, false).0.unwrap_err().to_string();
+        assert_eq!(
+            error("if a print 1;"),
+            "[line 1] Error at 'a': Expect '(' after 'if'."
+        );
+        assert_eq!(
+            error("while (a print 1;"),
+            "[line 1] Error at 'print': Expect ')' after condition."
+        );
+        assert_eq!(
+            error("if (a) var b;"),
+            "[line 1] Error at 'var': Expect expression."
+        );
+    }
+
+    #[test]
+    fn imports() {
+        let statements = parse("import \"lib/a.lox\" as a;", false).0.unwrap();
+        assert_eq!(statements[0].to_string(), "(import \"lib/a.lox\" a)");
+
+        let error = |source| parse(source, false).0.unwrap_err().to_string();
+        assert_eq!(
+            error("import a;"),
+            "[line 1] Error at 'a': Expect module path after 'import'."
+        );
+        assert_eq!(
+            error("import \"a.lox\" a;"),
+            "[line 1] Error at 'a': Expect 'as' after module path."
+        );
+        assert_eq!(
+            error("import \"a.lox\" as;"),
+            "[line 1] Error at ';': Expect module name after 'as'."
+        );
+    }
+
+    #[test]
+    fn calls() {
+        let statements = parse("f()(1, -2 + 3);", false).0.unwrap();
+        assert_eq!(
+            statements[0].to_string(),
+            "(; (call (call f) 1 (+ (- 2) 3)))"
+        );
+
+        let arguments = vec!["1"; 256].join(", ");
+        assert_eq!(
+            parse(&format!("f({});", arguments), false)
+                .0
+                .unwrap_err()
+                .to_string(),
+            "[line 1] Error at '1': Can't have more than 255 arguments."
+        );
+        assert_eq!(
+            parse("f(1;", false).0.unwrap_err().to_string(),
+            "[line 1] Error at ';': Expect ')' after arguments."
+        );
+    }
+
+    #[test]
+    fn trailing_expression() {
+        assert!(parse("1 + 2", false).0.is_err());
+        assert!(parse("var a = 1; a + 2", true).0.is_ok());
+        assert!(parse("var a = 1", true).0.is_err());
+    }
+
+    #[test]
+    fn incomplete() {
+        for source in ["{ var a = 1;", "print (1 +", "var a", "1 +"] {
+            let (result, at_end) = parse(source, true);
+            assert!(result.is_err() && at_end, "{}", source);
+        }
+
+        for source in ["1 + ;", ") + 1", "1 = 2"] {
+            let (result, at_end) = parse(source, true);
+            assert!(result.is_err() && !at_end, "{}", source);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let error = |source| parse(source, false).0.unwrap_err().to_string();
+
+        assert_eq!(error("1 + ;"), "[line 1] Error at ';': Expect expression.");
+        assert_eq!(
+            error("print 1"),
+            "[line 1] Error at end: Expect ';' after value."
+        );
+        assert_eq!(
+            error("var a;\n1 = 2;"),
+            "[line 2] Error at '=': Invalid assignment target."
+        );
+        assert_eq!(
+            error("{\nvar = 1;"),
+            "[line 2] Error at '=': Expect variable name."
+        );
+        assert_eq!(error("1 +\n@;"), "[line 2] Error: Unexpected character.");
+        assert_eq!(error("\"a\nb"), "[line 2] Error: Unterminated string.");
+    }
+
+    #[test]
+    fn nesting() {
+        let nested = |open: &str, close: &str, depth| {
+            format!("{}1{};", open.repeat(depth), close.repeat(depth))
+        };
+        let error = |source: &str| parse(source, false).0.unwrap_err().to_string();
+
+        // Each level of grouping nests the expression inside it once more.
+        // Trees as deep as are allowed can be walked recursively.
+        let statements = parse(&nested("(", ")", MAX_NESTING - 1), false).0.unwrap();
+        assert_eq!(statements.clone(), statements);
+        assert!(format!("{} {:?}", statements[0], statements).contains("1"));
+        assert_eq!(
+            error(&nested("(", ")", MAX_NESTING)),
+            "[line 1] Error at '1': Too much nesting."
+        );
+        assert_eq!(
+            error(&format!(
+                "{}print 1;{}",
+                "{".repeat(MAX_NESTING + 1),
+                "}".repeat(MAX_NESTING + 1)
+            )),
+            "[line 1] Error at 'print': Too much nesting."
+        );
+        assert_eq!(
+            error(&format!(
+                "print {};",
+                vec!["1"; MAX_NESTING + 1].join(" + ")
+            )),
+            "[line 1] Error at '1': Too much nesting."
+        );
+        assert_eq!(
+            error(&format!("f{};", "()".repeat(MAX_NESTING))),
+            "[line 1] Error at ')': Too much nesting."
+        );
+    }
+
+    /// Inputs like these used to overflow the stack within seconds of
+    /// fuzzing the parser.
+    #[test]
+    fn deep_input() {
+        let sources = [
+            format!("print {}1{};", "(".repeat(100_000), ")".repeat(100_000)),
+            format!("print {}1;", "-".repeat(100_000)),
+            "(".repeat(100_000),
+        ];
+
+        for source in sources {
+            let error = parse(&source, false).0.unwrap_err();
+            assert!(error.downcast_ref::<ParseError>().is_some());
+        }
+    }
+
+    #[test]
+    fn without_eof() {
+        assert!(Parser::new(Vec::new()).parse().unwrap().is_empty());
+    }
 }
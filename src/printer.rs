@@ -0,0 +1,481 @@
+use crate::{
+    expr::Expr,
+    interpreter::{RED_ZONE, STACK_SEGMENT},
+    stmt::Stmt,
+    token::Literal,
+    token_type::TokenType,
+};
+
+const INDENT: &str = "    ";
+
+/// Binding strength of each level of the expression grammar, loosest first.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+enum Precedence {
+    Assignment,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    fn of_operator(token_type: &TokenType) -> Self {
+        match token_type {
+            TokenType::BangEqual | TokenType::EqualEqual => Self::Equality,
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => Self::Comparison,
+            TokenType::Minus | TokenType::Plus => Self::Term,
+            TokenType::Slash | TokenType::Star | TokenType::Percent => Self::Factor,
+            _ => unreachable!(),
+        }
+    }
+
+    fn of(expr: &Expr) -> Self {
+        match expr {
+            Expr::Binary { operator, .. } => Self::of_operator(&operator.token_type),
+            Expr::Assign { .. } => Self::Assignment,
+            Expr::Unary { .. } => Self::Unary,
+            Expr::Call { .. } | Expr::Get { .. } | Expr::Index { .. } => Self::Call,
+            Expr::SetIndex { .. } => Self::Assignment,
+            Expr::Literal {
+                literal: Literal::Number(number),
+            } if number.is_nan() || number.is_infinite() => Self::Factor,
+            Expr::Literal {
+                literal: Literal::Number(number),
+            } if number.is_sign_negative() => Self::Unary,
+            Expr::Grouping { .. }
+            | Expr::Literal { .. }
+            | Expr::Variable { .. }
+            | Expr::List { .. }
+            | Expr::Map { .. } => Self::Primary,
+        }
+    }
+}
+
+/// Prints a program as canonically formatted Lox source.
+///
+/// Unlike the `Display` impls on `Stmt` and `Expr`, the output can be fed
+/// back into the parser. Groupings present in the tree are always kept, and
+/// any other parentheses are only added where precedence or associativity
+/// requires them, so printing a parsed program and parsing it again gives
+/// back the same tree.
+pub struct Printer {}
+
+impl Printer {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub fn print(&self, statements: &[Stmt]) -> String {
+        let mut output = String::new();
+        for statement in statements {
+            self.statement(&mut output, statement, 0);
+        }
+        output
+    }
+
+    fn statement(&self, output: &mut String, stmt: &Stmt, depth: usize) {
+        output.push_str(&INDENT.repeat(depth));
+
+        match stmt {
+            Stmt::Expression { expression } if self.starts_with_block(expression) => {
+                output.push_str(&format!("({});\n", self.expression(expression)));
+            }
+            Stmt::Expression { expression } => {
+                output.push_str(&format!("{};\n", self.expression(expression)));
+            }
+            Stmt::Print { expression } => {
+                output.push_str(&format!("print {};\n", self.expression(expression)));
+            }
+            Stmt::Var {
+                name,
+                initializer: Some(initializer),
+            } => output.push_str(&format!(
+                "var {} = {};\n",
+                name.get_lexeme(),
+                self.expression(initializer)
+            )),
+            Stmt::Var {
+                name,
+                initializer: None,
+            } => output.push_str(&format!("var {};\n", name.get_lexeme())),
+            Stmt::Block { statements } => {
+                output.push_str("{\n");
+                for statement in statements {
+                    self.statement(output, statement, depth + 1);
+                }
+                output.push_str(&INDENT.repeat(depth));
+                output.push_str("}\n");
+            }
+            Stmt::Import { path, name } => output.push_str(&format!(
+                "import {} as {};\n",
+                path.get_lexeme(),
+                name.get_lexeme()
+            )),
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => {
+                output.push_str(&format!(
+                    "for (var {} in {}) ",
+                    name.get_lexeme(),
+                    self.expression(iterable)
+                ));
+                self.body(output, body, depth);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                output.push_str(&format!("if ({}) ", self.expression(condition)));
+                match else_branch {
+                    // Otherwise the `else` would be read back as belonging
+                    // to the `if` the then branch ends with.
+                    Some(_) if self.ends_with_if(then_branch) => {
+                        let block = Stmt::Block {
+                            statements: vec![(**then_branch).clone()],
+                        };
+                        self.body(output, &block, depth);
+                    }
+                    _ => self.body(output, then_branch, depth),
+                }
+
+                if let Some(else_branch) = else_branch {
+                    output.pop();
+                    output.push_str(" else ");
+                    self.body(output, else_branch, depth);
+                }
+            }
+            Stmt::While { condition, body } => {
+                output.push_str(&format!("while ({}) ", self.expression(condition)));
+                self.body(output, body, depth);
+            }
+        }
+    }
+
+    /// Prints the body of a compound statement, which carries on from the
+    /// same line.
+    fn body(&self, output: &mut String, stmt: &Stmt, depth: usize) {
+        let mut inner = String::new();
+        self.statement(&mut inner, stmt, depth);
+        output.push_str(inner.trim_start());
+    }
+
+    /// Whether `stmt` ends with an `if` that has no `else`.
+    fn ends_with_if(&self, mut stmt: &Stmt) -> bool {
+        loop {
+            match stmt {
+                Stmt::If {
+                    else_branch: None, ..
+                } => return true,
+                Stmt::If {
+                    else_branch: Some(body),
+                    ..
+                }
+                | Stmt::While { body, .. }
+                | Stmt::ForIn { body, .. } => stmt = body,
+                _ => return false,
+            }
+        }
+    }
+
+    /// Whether `expr` would be read back as a block if it started a
+    /// statement, because it starts with a map that doesn't begin with a
+    /// simple key and a colon.
+    fn starts_with_block(&self, mut expr: &Expr) -> bool {
+        // Follow the leftmost operand down to what the statement starts
+        // with.
+        while let Expr::Binary { left: object, .. }
+        | Expr::Call { callee: object, .. }
+        | Expr::Get { object, .. }
+        | Expr::Index { object, .. }
+        | Expr::SetIndex { object, .. } = expr
+        {
+            expr = object;
+        }
+
+        match expr {
+            Expr::Map { entries, .. } => match entries.first() {
+                Some((Expr::Variable { .. }, _)) => false,
+                // Numbers that aren't printed as a single token.
+                Some((
+                    Expr::Literal {
+                        literal: Literal::Number(number),
+                    },
+                    _,
+                )) => !number.is_finite() || number.is_sign_negative(),
+                Some((Expr::Literal { .. }, _)) => false,
+                _ => true,
+            },
+            _ => false,
+        }
+    }
+
+    pub fn expression(&self, expr: &Expr) -> String {
+        // Grows the stack for the same reason as `Optimizer::expression`.
+        stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || self.format(expr))
+    }
+
+    fn format(&self, expr: &Expr) -> String {
+        match expr {
+            Expr::Literal { literal } => self.literal(literal),
+            Expr::Variable { name } => name.get_lexeme(),
+            Expr::Assign { name, value } => {
+                format!(
+                    "{} = {}",
+                    name.get_lexeme(),
+                    self.operand(value, Precedence::Assignment)
+                )
+            }
+            Expr::Grouping { expression } => format!("({})", self.expression(expression)),
+            Expr::Unary { operator, right } => {
+                format!(
+                    "{}{}",
+                    operator.get_lexeme(),
+                    self.operand(right, Precedence::Unary)
+                )
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let precedence = Precedence::of_operator(&operator.token_type);
+                let left = self.operand(left, precedence);
+                let right = if Precedence::of(right) <= precedence {
+                    format!("({})", self.expression(right))
+                } else {
+                    self.expression(right)
+                };
+
+                format!("{} {} {}", left, operator.get_lexeme(), right)
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                let arguments: Vec<String> = arguments
+                    .iter()
+                    .map(|argument| self.expression(argument))
+                    .collect();
+                format!(
+                    "{}({})",
+                    self.operand(callee, Precedence::Call),
+                    arguments.join(", ")
+                )
+            }
+            Expr::Get { object, name } => {
+                format!(
+                    "{}.{}",
+                    self.operand(object, Precedence::Call),
+                    name.get_lexeme()
+                )
+            }
+            Expr::List { elements } => {
+                let elements: Vec<String> = elements
+                    .iter()
+                    .map(|element| self.expression(element))
+                    .collect();
+                format!("[{}]", elements.join(", "))
+            }
+            Expr::Map { entries, .. } => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| {
+                        format!("{}: {}", self.expression(key), self.expression(value))
+                    })
+                    .collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+            Expr::Index { object, index, .. } => {
+                format!(
+                    "{}[{}]",
+                    self.operand(object, Precedence::Call),
+                    self.expression(index)
+                )
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                format!(
+                    "{}[{}] = {}",
+                    self.operand(object, Precedence::Call),
+                    self.expression(index),
+                    self.operand(value, Precedence::Assignment)
+                )
+            }
+        }
+    }
+
+    /// Prints `expr`, parenthesised if it binds more loosely than `minimum`.
+    fn operand(&self, expr: &Expr, minimum: Precedence) -> String {
+        if Precedence::of(expr) < minimum {
+            format!("({})", self.expression(expr))
+        } else {
+            self.expression(expr)
+        }
+    }
+
+    fn literal(&self, literal: &Literal) -> String {
+        match literal {
+            Literal::String(string) => format!("\"{}\"", string),
+            Literal::Number(number) if number.is_nan() => "0 / 0".to_owned(),
+            Literal::Number(number) if number.is_infinite() => {
+                if number.is_sign_negative() {
+                    "-1 / 0".to_owned()
+                } else {
+                    "1 / 0".to_owned()
+                }
+            }
+            _ => format!("{}", literal),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generator,
+        lexer::Lexer,
+        parser::Parser,
+        token::{Literal, Token},
+    };
+
+    use proptest::prelude::*;
+
+    fn parse(source: &str) -> Expr {
+        // Printing it rather than using an expression statement means a map
+        // at the start isn't taken for a block.
+        let tokens = Lexer::new(&format!("print {};", source)).scan_tokens();
+        match Parser::new(tokens).parse().unwrap().remove(0) {
+            Stmt::Print { expression } => expression,
+            _ => unreachable!(),
+        }
+    }
+
+    fn round_trip(source: &str) -> String {
+        Printer::new().expression(&parse(source))
+    }
+
+    #[test]
+    fn canonical_spacing() {
+        assert_eq!(round_trip("1+2*  3"), "1 + 2 * 3");
+        assert_eq!(round_trip("- 1 == !true"), "-1 == !true");
+        assert_eq!(round_trip("\"a\"+\"b\""), "\"a\" + \"b\"");
+    }
+
+    #[test]
+    fn keeps_groupings() {
+        assert_eq!(round_trip("(1 + 2) * 3"), "(1 + 2) * 3");
+        assert_eq!(round_trip("1 - (2 - 3)"), "1 - (2 - 3)");
+        assert_eq!(round_trip("((nil))"), "((nil))");
+    }
+
+    #[test]
+    fn assignment() {
+        assert_eq!(round_trip("a=b=1"), "a = b = 1");
+        assert_eq!(round_trip("a = (b = 1) + 2"), "a = (b = 1) + 2");
+    }
+
+    #[test]
+    fn calls() {
+        assert_eq!(round_trip("f ( 1,2 )( )"), "f(1, 2)()");
+        assert_eq!(round_trip("-f(a = 1)"), "-f(a = 1)");
+        assert_eq!(round_trip("(-f)()"), "(-f)()");
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(round_trip("[ 1,[ ] ] [0]"), "[1, []][0]");
+        assert_eq!(round_trip("xs [i] = xs . len ( )"), "xs[i] = xs.len()");
+        assert_eq!(round_trip("(-1).len"), "(-1).len");
+    }
+
+    #[test]
+    fn maps() {
+        assert_eq!(round_trip("{ 1 :{ } ,\"a\":b}"), "{1: {}, \"a\": b}");
+
+        let statement = |source: &str| {
+            let tokens = Lexer::new(&format!("print {};", source)).scan_tokens();
+            match Parser::new(tokens).parse().unwrap().remove(0) {
+                Stmt::Print { expression } => Stmt::Expression { expression },
+                _ => unreachable!(),
+            }
+        };
+        let statements = [
+            statement("{}.len()"),
+            statement("{-1: 2}[-1]"),
+            statement("{a: 1}[a]"),
+        ];
+
+        // Maps that would be read back as blocks are parenthesised.
+        assert_eq!(
+            Printer::new().print(&statements),
+            "({}.len());\n({-1: 2}[-1]);\n{a: 1}[a];\n"
+        );
+    }
+
+    #[test]
+    fn statements() {
+        let tokens = Lexer::new("var a=1;{var b;print a+b;{}} a=2;").scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+
+        assert_eq!(
+            Printer::new().print(&statements),
+            "var a = 1;\n{\n    var b;\n    print a + b;\n    {\n    }\n}\na = 2;\n"
+        );
+    }
+
+    #[test]
+    fn for_in() {
+        let tokens = Lexer::new("for(var x in xs)print x;for(var x in xs){x;}").scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+
+        assert_eq!(
+            Printer::new().print(&statements),
+            "for (var x in xs) print x;\nfor (var x in xs) {\n    x;\n}\n"
+        );
+    }
+
+    #[test]
+    fn adds_required_parentheses() {
+        let minus = Token::new(TokenType::Minus, "-".to_owned(), None, 1);
+        let number = |n| {
+            Box::new(Expr::Literal {
+                literal: Literal::Number(n),
+            })
+        };
+        let expr = Expr::Binary {
+            left: number(1.0),
+            operator: minus.clone(),
+            right: Box::new(Expr::Binary {
+                left: number(2.0),
+                operator: minus,
+                right: number(3.0),
+            }),
+        };
+
+        assert_eq!(Printer::new().expression(&expr), "1 - (2 - 3)");
+    }
+
+    proptest! {
+        #[test]
+        fn parse_print_parse(expr in generator::expr()) {
+            let printer = Printer::new();
+            let parsed = parse(&printer.expression(&expr));
+            let reparsed = parse(&printer.expression(&parsed));
+
+            prop_assert_eq!(parsed, reparsed);
+        }
+    }
+}
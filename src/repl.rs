@@ -0,0 +1,559 @@
+use std::{collections::HashMap, fs::read_to_string, path::PathBuf, time::Instant};
+
+use anyhow::{bail, Context as _, Error, Result};
+use rustyline::{
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::Validator,
+    Context, Editor, Helper,
+};
+
+use crate::{
+    describe, dump_ast, dump_tokens, exit_status, interpreter::Interpreter, lexer::Lexer, natives,
+    object::Object, optimizer::Optimizer, parser::Parser, stmt::Stmt, token_type::TokenType, Args,
+};
+
+const KEYWORDS: [&str; 19] = [
+    "and", "as", "class", "else", "false", "for", "fun", "if", "import", "in", "nil", "or",
+    "print", "return", "super", "this", "true", "var", "while",
+];
+
+/// Runs an interactive session on the terminal until the user ends it with
+/// Ctrl-D.
+pub fn start(args: &Args) -> Result<()> {
+    let mut session = Session::new(args);
+    let mut editor: Editor<LoxHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(LoxHelper::default()));
+
+    let history = history_path();
+    if let Some(history) = &history {
+        // There won't be a history file the first time round.
+        let _ = editor.load_history(history);
+    }
+
+    let mut input = String::new();
+    // Set if the program calls exit(), which ends the session.
+    let mut exit = None;
+
+    loop {
+        // Carry on from the previous line if it left a statement open.
+        let prompt = if input.is_empty() { "> " } else { "... " };
+
+        match editor.readline(prompt) {
+            Ok(line) if input.is_empty() && line.trim_start().starts_with(':') => {
+                match session.command(line.trim()) {
+                    Ok(()) => {}
+                    Err(e) if exit_status(&e).is_some() => exit = Some(e),
+                    Err(e) => session.report(&e),
+                }
+
+                editor.add_history_entry(line.trim())?;
+                if exit.is_some() {
+                    break;
+                }
+                if let Some(helper) = editor.helper_mut() {
+                    helper.refresh(&session);
+                }
+            }
+            Ok(line) => {
+                input.push_str(&line);
+                input.push('\n');
+
+                match session.eval(&input) {
+                    Ok(false) => continue,
+                    Ok(true) => {}
+                    Err(e) if exit_status(&e).is_some() => exit = Some(e),
+                    Err(e) => session.report(&e),
+                }
+
+                editor.add_history_entry(input.trim_end())?;
+                if exit.is_some() {
+                    break;
+                }
+                if let Some(helper) = editor.helper_mut() {
+                    helper.refresh(&session);
+                }
+                input.clear();
+            }
+            // Ctrl-C throws away what has been typed, but not the session.
+            Err(ReadlineError::Interrupted) => input.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    if let Some(history) = &history {
+        if let Err(e) = editor.save_history(history) {
+            eprintln!("Couldn't save history to {}: {}", history.display(), e);
+        }
+    }
+
+    exit.map_or(Ok(()), Err)
+}
+
+fn history_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".glad_history"))
+}
+
+/// An interpreter that keeps its state from one input to the next, for the
+/// REPL.
+pub struct Session {
+    interpreter: Interpreter,
+    args: Args,
+}
+
+impl Session {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            interpreter: args.interpreter(),
+            args: args.clone(),
+        }
+    }
+
+    /// Gives the next input its own budget, rather than sharing one across
+    /// the whole session.
+    fn refuel(&mut self) {
+        self.interpreter
+            .set_budget(self.args.fuel, self.args.deadline());
+    }
+
+    /// Runs a chunk of REPL input, printing the value of any expression
+    /// statements in it. If the input stops part way through a statement,
+    /// nothing is run and this returns `Ok(false)`, so the caller can read
+    /// more and try again with the whole lot.
+    pub fn eval(&mut self, source: &str) -> Result<bool> {
+        let tokens = Lexer::new(source).scan_tokens();
+        // A string left open at the end carries on onto the next line.
+        let open_string = tokens.iter().rev().nth(1).is_some_and(|token| {
+            token.token_type == TokenType::Error && token.get_lexeme().starts_with('"')
+        });
+        let mut parser = Parser::new(tokens).repl();
+
+        let mut statements = match parser.parse() {
+            Ok(statements) => statements,
+            Err(_) if parser.is_at_end() || open_string => return Ok(false),
+            Err(error) => return Err(error),
+        };
+
+        if self.args.optimize {
+            statements = Optimizer::new().optimize(&statements);
+        }
+
+        // Expression statements show their values. Running the result as a
+        // program unwinds errors the same way as a script.
+        let statements: Vec<Stmt> = statements
+            .into_iter()
+            .map(|statement| match statement {
+                Stmt::Expression { expression } => Stmt::Print { expression },
+                statement => statement,
+            })
+            .collect();
+
+        self.refuel();
+        self.interpreter.interpret(&statements)?;
+
+        Ok(true)
+    }
+
+    /// Runs one of the colon-prefixed meta-commands, such as `:env`.
+    pub fn command(&mut self, line: &str) -> Result<()> {
+        let (command, argument) = match line.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, ""),
+        };
+
+        match command {
+            ":help" => self.interpreter.write(HELP)?,
+            ":env" => {
+                let mut env = String::new();
+                for (name, value) in self.interpreter.globals() {
+                    match value {
+                        Object::String(string) => {
+                            env.push_str(&format!("{} = {:?}\n", name, string))
+                        }
+                        value => env.push_str(&format!("{} = {}\n", name, value)),
+                    }
+                }
+                self.interpreter.write(&env)?;
+            }
+            ":load" => {
+                if argument.is_empty() {
+                    bail!("usage: :load <file>");
+                }
+
+                let source = read_to_string(argument)
+                    .with_context(|| format!("couldn't read {}", argument))?;
+                let mut statements = Parser::new(Lexer::new(&source).scan_tokens()).parse()?;
+                if self.args.optimize {
+                    statements = Optimizer::new().optimize(&statements);
+                }
+                self.refuel();
+                self.interpreter
+                    .interpret_file(&statements, PathBuf::from(argument))?;
+            }
+            ":ast" => {
+                let tokens = Lexer::new(argument).scan_tokens();
+                let statements = Parser::new(tokens).repl().parse()?;
+                self.interpreter.write(&dump_ast(&statements, false))?;
+            }
+            ":tokens" => {
+                self.interpreter
+                    .write(&dump_tokens(&Lexer::new(argument).scan_tokens()))?;
+            }
+            ":time" => {
+                let start = Instant::now();
+                if !self.eval(argument)? {
+                    bail!("incomplete input");
+                }
+                self.interpreter
+                    .write(&format!("took {:?}\n", start.elapsed()))?;
+            }
+            ":reset" => self.interpreter.reset(),
+            _ => bail!("unknown command '{}', try :help", command),
+        }
+
+        Ok(())
+    }
+
+    /// Shows an error the way the command line would, through the
+    /// interpreter's error sink.
+    pub fn report(&self, error: &Error) {
+        self.interpreter.report(&describe(error));
+    }
+
+    fn global_names(&self) -> Vec<String> {
+        self.interpreter
+            .globals()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// The names that can follow a dot after each global: a module's
+    /// globals, or the methods of a list, map or string.
+    fn member_names(&self) -> HashMap<String, Vec<String>> {
+        self.interpreter
+            .globals()
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let mut members: Vec<String> = match &value {
+                    Object::Module(module) => module
+                        .globals
+                        .borrow()
+                        .values()
+                        .into_iter()
+                        .map(|(name, _)| name)
+                        .collect(),
+                    _ => natives::methods(&value)
+                        .iter()
+                        .map(|method| method.name.to_owned())
+                        .collect(),
+                };
+                members.sort_unstable();
+
+                (!members.is_empty()).then_some((name, members))
+            })
+            .collect()
+    }
+}
+
+const HELP: &str = "\
+:help           show this message
+:env            list global variables and their values
+:load <file>    run a file in this session
+:ast <code>     show the syntax tree for some code without running it
+:tokens <code>  show the tokens in some code
+:time <code>    run some code and show how long it took
+:reset          forget everything defined so far
+";
+
+/// Tab-completes keywords and the globals defined so far in the session,
+/// and after a dot, the members of the global before it.
+#[derive(Default)]
+struct LoxHelper {
+    globals: Vec<String>,
+    members: HashMap<String, Vec<String>>,
+}
+
+impl LoxHelper {
+    /// Catches up with what the session has defined.
+    fn refresh(&mut self, session: &Session) {
+        self.globals = session.global_names();
+        self.members = session.member_names();
+    }
+}
+
+/// Where the word that ends at `end` starts.
+fn word_start(line: &str, end: usize) -> usize {
+    line[..end]
+        .char_indices()
+        .rev()
+        .find(|(_, c)| !c.is_alphanumeric() && *c != '_')
+        .map_or(0, |(i, c)| i + c.len_utf8())
+}
+
+impl Completer for LoxHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = word_start(line, pos);
+        let prefix = &line[start..pos];
+
+        let mut names: Vec<&str> = if line[..start].ends_with('.') {
+            // Only a global's members are known; anything longer, like
+            // `a.b.`, would mean evaluating the expression.
+            let dot = start - 1;
+            let receiver = word_start(line, dot);
+            let members = if line[..receiver].ends_with('.') {
+                None
+            } else {
+                self.members.get(&line[receiver..dot])
+            };
+
+            members
+                .into_iter()
+                .flatten()
+                .map(String::as_str)
+                .filter(|name| name.starts_with(prefix))
+                .collect()
+        } else {
+            KEYWORDS
+                .iter()
+                .copied()
+                .chain(self.globals.iter().map(String::as_str))
+                .filter(|name| name.starts_with(prefix))
+                .collect()
+        };
+        names.sort_unstable();
+        names.dedup();
+
+        let candidates = names
+            .into_iter()
+            .map(|name| Pair {
+                display: name.to_owned(),
+                replacement: name.to_owned(),
+            })
+            .collect();
+
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for LoxHelper {
+    type Hint = String;
+}
+
+impl Highlighter for LoxHelper {}
+
+impl Validator for LoxHelper {}
+
+impl Helper for LoxHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{buffer::Buffer, capabilities::Capabilities};
+
+    /// The globals the session has defined, leaving out the natives.
+    fn defined(session: &Session) -> Vec<String> {
+        let natives = Session::new(&Args::default()).global_names();
+        session
+            .global_names()
+            .into_iter()
+            .filter(|name| !natives.contains(name))
+            .collect()
+    }
+
+    fn complete(line: &str, globals: &[&str]) -> (usize, Vec<String>) {
+        let helper = LoxHelper {
+            globals: globals.iter().map(|name| name.to_string()).collect(),
+            ..LoxHelper::default()
+        };
+        complete_with(&helper, line)
+    }
+
+    fn complete_with(helper: &LoxHelper, line: &str) -> (usize, Vec<String>) {
+        let history = DefaultHistory::new();
+        let (start, candidates) = helper
+            .complete(line, line.len(), &Context::new(&history))
+            .unwrap();
+
+        (
+            start,
+            candidates
+                .into_iter()
+                .map(|candidate| candidate.replacement)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn keywords() {
+        assert_eq!(complete("va", &[]), (0, vec!["var".to_owned()]));
+        assert_eq!(
+            complete("print t", &[]),
+            (6, vec!["this".to_owned(), "true".to_owned()])
+        );
+    }
+
+    #[test]
+    fn globals() {
+        assert_eq!(
+            complete("1 + vé", &["vélo", "value"]),
+            (4, vec!["vélo".to_owned()])
+        );
+        assert_eq!(
+            complete("{v", &["value"]),
+            (1, vec!["value".to_owned(), "var".to_owned()])
+        );
+    }
+
+    #[test]
+    fn members() {
+        let mut session = Session::new(&Args::default());
+        session
+            .eval("import \"tests/lox/import/lib/shapes.lox\" as shapes;\nvar s = \"ab\";\nvar n = 1;")
+            .unwrap();
+        let mut helper = LoxHelper::default();
+        helper.refresh(&session);
+
+        assert_eq!(
+            complete_with(&helper, "shapes."),
+            (
+                7,
+                vec!["name".to_owned(), "sides".to_owned(), "units".to_owned()]
+            )
+        );
+        assert_eq!(
+            complete_with(&helper, "print s.s"),
+            (
+                8,
+                vec![
+                    "split".to_owned(),
+                    "startsWith".to_owned(),
+                    "substr".to_owned()
+                ]
+            )
+        );
+        assert_eq!(complete_with(&helper, "n."), (2, Vec::new()));
+        assert_eq!(complete_with(&helper, "shapes.units.f"), (13, Vec::new()));
+    }
+
+    #[test]
+    fn sessions_keep_globals() {
+        let mut session = Session::new(&Args::default());
+        let output = Buffer::new();
+        session.interpreter = Interpreter::new().output(output.clone());
+
+        session.eval("var a = 1;").unwrap();
+        assert!(!session.eval("{ var b = 2;").unwrap());
+        assert!(!session.eval("var s = \"open").unwrap());
+        session.eval("var c = 3;").unwrap();
+
+        assert_eq!(defined(&session), vec!["a", "c"]);
+
+        session.eval("a + c;\nprint a;").unwrap();
+        assert_eq!(output.contents(), "4\n1\n");
+    }
+
+    #[test]
+    fn reports_stack_traces() {
+        let mut session = Session::new(&Args::default());
+        let errors = Buffer::new();
+        session.interpreter = Interpreter::new().errors(errors.clone());
+
+        let error = session.eval("var a = 1;\n-\"a\";").unwrap_err();
+        session.report(&error);
+        assert_eq!(
+            errors.contents(),
+            "Operand must be a number.\n[line 2] in script\n"
+        );
+    }
+
+    #[test]
+    fn sandboxed_output() {
+        let mut session = Session::new(&Args::default());
+        let output = Buffer::new();
+        session.interpreter = Interpreter::new()
+            .capabilities(Capabilities::none())
+            .output(output.clone());
+
+        let error = session.eval("var a = 1;\na + 1").unwrap_err();
+        assert_eq!(error.to_string(), "Not allowed to write to stdout.");
+        assert_eq!(output.contents(), "");
+    }
+
+    #[test]
+    fn commands() {
+        let mut session = Session::new(&Args::default());
+        let output = Buffer::new();
+        let errors = Buffer::new();
+        session.interpreter = Interpreter::new()
+            .output(output.clone())
+            .errors(errors.clone());
+
+        let path = std::env::temp_dir().join(format!("glad-load-{}.lox", std::process::id()));
+        std::fs::write(&path, "var a = 1;\nvar b = a + 1;\n").unwrap();
+        session
+            .command(&format!(":load {}", path.display()))
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let globals = session.interpreter.globals();
+        assert!(globals.contains(&("a".to_owned(), Object::Number(1.0))));
+        assert!(globals.contains(&("b".to_owned(), Object::Number(2.0))));
+
+        // Imports are found next to the loaded file.
+        let root = std::env::temp_dir().join(format!("glad-load-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("lib")).unwrap();
+        std::fs::write(
+            root.join("main.lox"),
+            "import \"lib/m.lox\" as m;\nvar d = m.x;\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("lib/m.lox"), "var x = 4;\n").unwrap();
+        session
+            .command(&format!(":load {}", root.join("main.lox").display()))
+            .unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        let globals = session.interpreter.globals();
+        assert!(globals.contains(&("d".to_owned(), Object::Number(4.0))));
+
+        session.command(":ast var c = b;").unwrap();
+        session.command(":tokens c").unwrap();
+        session.command(":env").unwrap();
+        session.command(":help").unwrap();
+        session.command(":time a + b").unwrap();
+        let output = output.contents();
+        assert!(output.starts_with("(var c b)\n  1 Identifier c\n  1 Eof \n"));
+        assert!(output.contains("\na = 1\nabs = <native fn abs>\n"));
+        let (output, time) = output.rsplit_once("3\ntook ").unwrap();
+        assert!(output.ends_with(HELP));
+        assert!(time.ends_with("s\n"));
+
+        session.command(":time c = 3").unwrap_err();
+        let error = session.command(":time exit(5);").unwrap_err();
+        assert_eq!(exit_status(&error), Some(5));
+        assert_eq!(defined(&session), vec!["a", "b", "d", "m"]);
+
+        session.command(":reset").unwrap();
+        assert!(defined(&session).is_empty());
+        assert!(session.global_names().contains(&"clock".to_owned()));
+
+        assert!(session.command(":ast 1 +").is_err());
+        assert!(session.command(":load").is_err());
+        let error = session.command(":nope").unwrap_err();
+        session.report(&error);
+        assert_eq!(
+            errors.contents(),
+            "Error: unknown command ':nope', try :help\n"
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use std::fmt;
+
+use crate::{expr::Expr, token::Token};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Expression {
+        expression: Expr,
+    },
+    Print {
+        expression: Expr,
+    },
+    Var {
+        name: Token,
+        initializer: Option<Expr>,
+    },
+    Block {
+        statements: Vec<Stmt>,
+    },
+    /// `import "path" as name;`
+    Import {
+        path: Token,
+        name: Token,
+    },
+    /// `if (condition) then_branch else else_branch`, where the `else` part
+    /// is optional.
+    If {
+        condition: Expr,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+    },
+    /// `while (condition) body`.
+    While {
+        condition: Expr,
+        body: Box<Stmt>,
+    },
+    /// `for (var name in iterable) body`.
+    ForIn {
+        name: Token,
+        iterable: Expr,
+        body: Box<Stmt>,
+    },
+}
+
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Expression { expression } => write!(f, "(; {})", expression),
+            Self::Print { expression } => write!(f, "(print {})", expression),
+            Self::Var {
+                name,
+                initializer: Some(initializer),
+            } => write!(f, "(var {} {})", name.get_lexeme(), initializer),
+            Self::Var {
+                name,
+                initializer: None,
+            } => write!(f, "(var {})", name.get_lexeme()),
+            Self::Block { statements } => {
+                write!(f, "(block")?;
+                for statement in statements {
+                    write!(f, " {}", statement)?;
+                }
+                write!(f, ")")
+            }
+            Self::Import { path, name } => {
+                write!(f, "(import {} {})", path.get_lexeme(), name.get_lexeme())
+            }
+            Self::If {
+                condition,
+                then_branch,
+                else_branch: Some(else_branch),
+            } => write!(f, "(if {} {} {})", condition, then_branch, else_branch),
+            Self::If {
+                condition,
+                then_branch,
+                else_branch: None,
+            } => write!(f, "(if {} {})", condition, then_branch),
+            Self::While { condition, body } => write!(f, "(while {} {})", condition, body),
+            Self::ForIn {
+                name,
+                iterable,
+                body,
+            } => write!(f, "(for-in {} {} {})", name.get_lexeme(), iterable, body),
+        }
+    }
+}
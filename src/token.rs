@@ -1,10 +1,10 @@
-use std::fmt;
+use std::{fmt, sync::Arc};
 
 use crate::token_type::TokenType;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
-    String(String),
+    String(Arc<str>),
     Number(f32),
     Bool(bool),
     Nil,
@@ -21,7 +21,7 @@ impl fmt::Display for Literal {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
     lexeme: String,
@@ -47,6 +47,10 @@ impl Token {
     pub fn get_lexeme(&self) -> String {
         self.lexeme.clone()
     }
+
+    pub fn get_line(&self) -> usize {
+        self.line
+    }
 }
 
 impl fmt::Display for Token {
@@ -5,9 +5,13 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
+    Colon,
     Dot,
     Minus,
+    Percent,
     Plus,
     Semicolon,
     Slash,
@@ -30,12 +34,15 @@ pub enum TokenType {
 
     // Keywords.
     And,
+    As,
     Class,
     Else,
     False,
     Fun,
     For,
     If,
+    Import,
+    In,
     Nil,
     Or,
     Print,
@@ -46,5 +53,12 @@ pub enum TokenType {
     Var,
     While,
 
+    // Trivia, only produced when the lexer is asked to keep it.
+    Comment,
+    Whitespace,
+
+    // Source the lexer couldn't make sense of, only produced in lossless mode.
+    Error,
+
     Eof,
 }
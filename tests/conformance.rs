@@ -0,0 +1,254 @@
+//! Runs every `.lox` file under `tests/lox` through the `glad` binary and
+//! checks what it prints against the annotations in the file, using the
+//! conventions of the Crafting Interpreters test suite:
+//!
+//! - `// expect: <output>` for each line the program should print
+//! - `// expect runtime error: <message>` for a runtime error on that line
+//! - `// Error at ...` or `// [line N] Error at ...` for a compile error
+//!
+//! Set `LOX_TEST_DIR` to run the files in another directory instead, such
+//! as a checkout of the upstream suite. Every file is run both with and
+//! without `-O`.
+//!
+//! Files known not to pass can be listed in an `xfail.txt` at the top of
+//! the directory, one path relative to it per line, with `#` starting a
+//! comment. A directory covers every file under it. Listed files may fail
+//! without failing the test, but one that passes does fail it, as does a
+//! line that matches nothing, so the list can't go stale. Prefix a line with `skip ` instead to not run the file
+//! at all, say because it never finishes.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const EXIT_COMPILE_ERROR: i32 = 65;
+const EXIT_RUNTIME_ERROR: i32 = 70;
+
+#[derive(Debug, Default)]
+struct Expectations {
+    output: Vec<String>,
+    compile_errors: Vec<String>,
+    /// The message, and the line it should be reported on.
+    runtime_error: Option<(String, usize)>,
+}
+
+impl Expectations {
+    fn parse(source: &str) -> Self {
+        let mut expectations = Self::default();
+
+        for (index, line) in source.lines().enumerate() {
+            let number = index + 1;
+            let comment = match line.find("// ") {
+                Some(start) => &line[start + 3..],
+                None => continue,
+            };
+
+            if let Some(output) = comment.strip_prefix("expect: ") {
+                expectations.output.push(output.to_owned());
+            } else if let Some(message) = comment.strip_prefix("expect runtime error: ") {
+                expectations.runtime_error = Some((message.to_owned(), number));
+            } else if comment.starts_with("Error") {
+                expectations
+                    .compile_errors
+                    .push(format!("[line {}] {}", number, comment));
+            } else if let Some(error) = comment
+                .strip_prefix("[line ")
+                .or_else(|| comment.strip_prefix("[java line "))
+            {
+                if error.contains("] Error") {
+                    expectations.compile_errors.push(format!("[line {}", error));
+                }
+            }
+        }
+
+        expectations
+    }
+
+    fn exit_code(&self) -> i32 {
+        if !self.compile_errors.is_empty() {
+            EXIT_COMPILE_ERROR
+        } else if self.runtime_error.is_some() {
+            EXIT_RUNTIME_ERROR
+        } else {
+            0
+        }
+    }
+}
+
+/// Runs one script, returning what was wrong with the result.
+fn check(path: &Path, optimize: bool) -> Vec<String> {
+    let source = fs::read_to_string(path).unwrap();
+    let expectations = Expectations::parse(&source);
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_glad"));
+    command.arg(path);
+    if optimize {
+        command.arg("-O");
+    }
+    let output = command.output().unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout: Vec<&str> = stdout.lines().collect();
+    let stderr: Vec<&str> = stderr.lines().collect();
+
+    let mut failures = Vec::new();
+
+    if stdout != expectations.output {
+        failures.push(format!(
+            "expected output {:?}, got {:?}",
+            expectations.output, stdout
+        ));
+    }
+
+    match &expectations.runtime_error {
+        Some((message, line)) => {
+            if stderr.first() != Some(&message.as_str()) {
+                failures.push(format!(
+                    "expected runtime error {:?}, got {:?}",
+                    message, stderr
+                ));
+            } else if !stderr
+                .get(1)
+                .is_some_and(|trace| trace.starts_with(&format!("[line {}]", line)))
+            {
+                failures.push(format!(
+                    "expected runtime error on line {}, got {:?}",
+                    line, stderr
+                ));
+            }
+        }
+        None => {
+            if stderr != expectations.compile_errors {
+                failures.push(format!(
+                    "expected errors {:?}, got {:?}",
+                    expectations.compile_errors, stderr
+                ));
+            }
+        }
+    }
+
+    if output.status.code() != Some(expectations.exit_code()) {
+        failures.push(format!(
+            "expected exit code {}, got {:?}",
+            expectations.exit_code(),
+            output.status.code()
+        ));
+    }
+
+    failures
+}
+
+/// The files in `xfail.txt` that are expected to fail, and the ones to
+/// skip.
+#[derive(Debug, Default)]
+struct Exceptions {
+    xfail: Vec<PathBuf>,
+    skip: Vec<PathBuf>,
+}
+
+impl Exceptions {
+    fn read(dir: &Path) -> Self {
+        let mut exceptions = Self::default();
+        let list = match fs::read_to_string(dir.join("xfail.txt")) {
+            Ok(list) => list,
+            Err(_) => return exceptions,
+        };
+
+        for line in list.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            match line.strip_prefix("skip ") {
+                Some(path) => exceptions.skip.push(PathBuf::from(path.trim())),
+                None => exceptions.xfail.push(PathBuf::from(line)),
+            }
+        }
+
+        exceptions
+    }
+
+    fn covers(list: &[PathBuf], name: &Path) -> bool {
+        list.iter().any(|entry| name.starts_with(entry))
+    }
+}
+
+fn lox_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            lox_files(&path, files);
+        } else if path.extension().is_some_and(|extension| extension == "lox") {
+            files.push(path);
+        }
+    }
+}
+
+#[test]
+fn conformance() {
+    let dir = env::var_os("LOX_TEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox"));
+
+    let mut files = Vec::new();
+    lox_files(&dir, &mut files);
+    files.sort();
+
+    let exceptions = Exceptions::read(&dir);
+    let mut failed = 0;
+    let mut unexpected_passes = Vec::new();
+
+    for file in &files {
+        let name = file.strip_prefix(&dir).unwrap();
+        if Exceptions::covers(&exceptions.skip, name) {
+            println!("SKIP {}", name.display());
+            continue;
+        }
+        let xfail = Exceptions::covers(&exceptions.xfail, name);
+        let mut passed = true;
+
+        for optimize in [false, true] {
+            let flags = if optimize { " -O" } else { "" };
+            let failures = check(file, optimize);
+
+            if failures.is_empty() {
+                println!("PASS {}{}", name.display(), flags);
+            } else if xfail {
+                println!("XFAIL {}{}", name.display(), flags);
+                passed = false;
+            } else {
+                println!("FAIL {}{}", name.display(), flags);
+                for failure in &failures {
+                    println!("     {}", failure);
+                }
+                failed += 1;
+            }
+        }
+
+        if xfail && passed {
+            println!("XPASS {}", name.display());
+            unexpected_passes.push(name.display().to_string());
+        }
+    }
+
+    assert!(!files.is_empty(), "no tests in {}", dir.display());
+    for entry in exceptions.xfail.iter().chain(&exceptions.skip) {
+        assert!(
+            files
+                .iter()
+                .any(|file| file.strip_prefix(&dir).unwrap().starts_with(entry)),
+            "{} in xfail.txt matches no tests",
+            entry.display()
+        );
+    }
+    assert_eq!(failed, 0, "{} of {} runs failed", failed, files.len() * 2);
+    assert!(
+        unexpected_passes.is_empty(),
+        "expected to fail, but passed: {:?}; take them out of xfail.txt",
+        unexpected_passes
+    );
+}
@@ -0,0 +1,84 @@
+//! Golden-file tests for the lexer, the parser and error messages.
+//!
+//! For every `tests/snapshots/<name>.lox` there are checked-in snapshots of
+//! what `glad` prints for it: `<name>.tokens` from `glad tokens`,
+//! `<name>.ast` from `glad ast` and `<name>.stderr` from running it, along
+//! with the exit code. Run with `BLESS=1` to write the current output over
+//! the snapshots, then review the diff.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Runs `glad` from the snapshot directory, so paths in its output don't
+/// depend on where the repo is checked out.
+fn glad(dir: &Path, args: &[&str]) -> (String, String, Option<i32>) {
+    let output = Command::new(env!("CARGO_BIN_EXE_glad"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap();
+
+    (
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+        output.status.code(),
+    )
+}
+
+/// Compares `actual` with the snapshot at `path`, or overwrites the
+/// snapshot when blessing. Returns a description of any mismatch.
+fn compare(path: &Path, actual: &str, bless: bool) -> Option<String> {
+    if bless {
+        fs::write(path, actual).unwrap();
+        return None;
+    }
+
+    match fs::read_to_string(path) {
+        Ok(expected) if expected == actual => None,
+        Ok(expected) => Some(format!(
+            "{} differs\n--- expected\n{}--- actual\n{}",
+            path.display(),
+            expected,
+            actual
+        )),
+        Err(_) => Some(format!(
+            "{} is missing, run with BLESS=1 to create it",
+            path.display()
+        )),
+    }
+}
+
+#[test]
+fn snapshots() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+    let bless = env::var_os("BLESS").is_some_and(|bless| bless != "0");
+
+    let mut fixtures: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "lox"))
+        .collect();
+    fixtures.sort();
+
+    let mut mismatches = Vec::new();
+
+    for fixture in &fixtures {
+        let name = fixture.file_name().unwrap().to_str().unwrap();
+
+        let (tokens, _, _) = glad(&dir, &["tokens", name]);
+        let (ast, _, _) = glad(&dir, &["ast", name]);
+        let (_, stderr, code) = glad(&dir, &["run", name]);
+        let diagnostics = format!("{}[exit code {}]\n", stderr, code.unwrap_or(-1));
+
+        for (extension, actual) in [("tokens", tokens), ("ast", ast), ("stderr", diagnostics)] {
+            let snapshot = fixture.with_extension(extension);
+            mismatches.extend(compare(&snapshot, &actual, bless));
+        }
+    }
+
+    assert!(!fixtures.is_empty(), "no fixtures in {}", dir.display());
+    assert!(mismatches.is_empty(), "\n{}", mismatches.join("\n"));
+}